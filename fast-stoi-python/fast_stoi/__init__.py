@@ -4,10 +4,15 @@ import warnings
 
 import numpy as np
 
+from .fast_stoi import band_center_freqs as band_center_freqs_internal  # type: ignore
 from .fast_stoi import par_stoi as par_stoi_internal  # type: ignore
+from .fast_stoi import Stoi as StoiInternal  # type: ignore
 from .fast_stoi import stoi as stoi_internal  # type: ignore
+from .fast_stoi import stoi_bands as stoi_bands_internal  # type: ignore
 
-__all__ = ["stoi", "STOI"]
+__all__ = ["stoi", "stoi_bands", "band_center_freqs", "Stoi", "STOI"]
+
+NUM_BANDS = len(band_center_freqs_internal())
 
 ERROR_MESSAGE = (
     "Not enough STFT frames to compute intermediate "
@@ -50,6 +55,88 @@ def stoi(x: np.ndarray, y: np.ndarray, fs_sig: int, extended=False) -> np.ndarra
     return np.array(out)
 
 
+def stoi_bands(x: np.ndarray, y: np.ndarray, fs_sig: int, extended=False) -> np.ndarray:
+    """
+    Compute the per-band Short-Time Objective Intelligibility (STOI) scores between two signals.
+    The GIL is released during the computation.
+    Args:
+        x: Clean speech signal (1D array).
+        y: Processed speech signal (1D array).
+        fs_sig: Sampling frequency of the signals (must be positive).
+        extended: Whether to use the extended STOI measure (default: False).
+    Returns:
+        Array of shape (15,) with one score per one-third octave band,
+        whose center frequencies are given by `band_center_freqs()`.
+        Their mean is the STOI score.
+    """
+
+    assert fs_sig > 0, "fs_sig must be positive"
+    assert x.shape == y.shape, "x and y must be of the same shapes"
+    assert len(x.shape) == 1, "Arrays must be 1D"
+
+    if x.dtype != np.float32:
+        x = x.astype(np.float32)
+    if y.dtype != np.float32:
+        y = y.astype(np.float32)
+
+    try:
+        return stoi_bands_internal(x, y, fs_sig, extended)
+    except Warning:
+        warnings.warn(ERROR_MESSAGE)
+        return np.full(NUM_BANDS, 1e-5, dtype=np.float32)
+
+
+class Stoi:
+    """
+    Reusable Short-Time Objective Intelligibility (STOI) scorer,
+    for many computations with the same parameters.
+    The resampling filter for `fs` is generated on construction.
+    Args:
+        fs: Sampling frequency of the signals (must be positive).
+        extended: Whether to use the extended STOI measure (default: False).
+    """
+
+    def __init__(self, fs: int, extended: bool = False):
+        assert fs > 0, "fs must be positive"
+        self.fs = fs
+        self.extended = extended
+        self._inner = StoiInternal(fs=fs, extended=extended)
+
+    def score(self, x: np.ndarray, y: np.ndarray) -> np.ndarray:
+        """
+        Compute the STOI measure between two signals.
+        The GIL is released during the computation.
+        Args:
+            x: Clean speech signal (1D array).
+            y: Processed speech signal (1D array).
+        """
+
+        assert x.shape == y.shape, "x and y must be of the same shapes"
+        assert len(x.shape) == 1, "Arrays must be 1D"
+
+        if x.dtype != np.float32:
+            x = x.astype(np.float32)
+        if y.dtype != np.float32:
+            y = y.astype(np.float32)
+
+        try:
+            out = self._inner.score(x, y)
+        except Warning:
+            warnings.warn(ERROR_MESSAGE)
+            out = 1e-5
+
+        return np.array(out)
+
+
+def band_center_freqs() -> np.ndarray:
+    """
+    Center frequencies in Hz of the 15 one-third octave bands used by STOI,
+    as an array of shape (15,).
+    """
+
+    return band_center_freqs_internal()
+
+
 try:
     from torch import Tensor, nn, tensor
 
@@ -25,6 +25,75 @@ mod fast_stoi {
         }
     }
 
+    #[pyfunction]
+    fn stoi_bands<'py>(
+        py: Python<'py>,
+        x: PyReadonlyArray1<'_, f32>,
+        y: PyReadonlyArray1<'_, f32>,
+        fs_sig: usize,
+        extended: bool,
+    ) -> PyResult<Bound<'py, PyArray1<f32>>> {
+        let x = x.as_slice().expect("x is not contiguous");
+        let y = y.as_slice().expect("y is not contiguous");
+        let config = lib_fast_stoi::StoiConfig {
+            extended,
+            ..Default::default()
+        };
+
+        match py.detach(|| lib_fast_stoi::stoi_bands(x, y, fs_sig, &config)) {
+            Ok(bands) => Ok(bands.into_pyarray(py)),
+            Err(err) => Err(PyWarning::new_err(err.to_string())),
+        }
+    }
+
+    /// Reusable STOI scorer for a fixed sampling frequency and configuration
+    #[pyclass]
+    struct Stoi {
+        fs_sig: usize,
+        config: lib_fast_stoi::StoiConfig,
+    }
+
+    #[pymethods]
+    impl Stoi {
+        #[new]
+        #[pyo3(signature = (fs, extended=false))]
+        fn new(fs: usize, extended: bool) -> Self {
+            // Generate the resampling filter ahead of the first score
+            // Invalid frequencies are reported by the first score instead
+            if fs != lib_fast_stoi::FS {
+                let _ = lib_fast_stoi::try_resample(&[], fs, lib_fast_stoi::FS);
+            }
+
+            Self {
+                fs_sig: fs,
+                config: lib_fast_stoi::StoiConfig {
+                    extended,
+                    ..Default::default()
+                },
+            }
+        }
+
+        fn score(
+            &self,
+            py: Python<'_>,
+            x: PyReadonlyArray1<'_, f32>,
+            y: PyReadonlyArray1<'_, f32>,
+        ) -> PyResult<f32> {
+            let x = x.as_slice().expect("x is not contiguous");
+            let y = y.as_slice().expect("y is not contiguous");
+
+            match py.detach(|| lib_fast_stoi::stoi_with_config(x, y, self.fs_sig, &self.config)) {
+                Ok(value) => Ok(value),
+                Err(err) => Err(PyWarning::new_err(err.to_string())),
+            }
+        }
+    }
+
+    #[pyfunction]
+    fn band_center_freqs<'py>(py: Python<'py>) -> Bound<'py, PyArray1<f32>> {
+        lib_fast_stoi::band_center_freqs().to_vec().into_pyarray(py)
+    }
+
     #[pyfunction]
     fn par_stoi<'py>(
         py: Python<'py>,
@@ -40,15 +109,13 @@ mod fast_stoi {
             .into_par_iter()
             .zip(y.outer_iter().into_par_iter())
             .map(|(x, y)| {
-                match lib_fast_stoi::stoi(
+                lib_fast_stoi::stoi(
                     x.as_slice().expect("x is not contiguous"),
                     y.as_slice().expect("y is not contiguous"),
                     fs_sig,
                     extended,
-                ) {
-                    Ok(value) => value,
-                    Err(_) => 1e-5,
-                }
+                )
+                .unwrap_or(1e-5)
             })
             .collect::<Vec<_>>()
             .into_pyarray(py)
@@ -1,6 +1,8 @@
 import numpy as np
+import pytest
 from pystoi import stoi as theirs
 
+from fast_stoi import Stoi, band_center_freqs, stoi_bands
 from fast_stoi import stoi as ours
 
 
@@ -35,3 +37,97 @@ def test_extended():
             )
 
     assert np.array(values).max() < 1e-7
+
+
+def test_extended_quiet():
+    # Extended normalization must not depend on the signal level
+    np.random.seed(42)
+    sr = 16_000
+    seconds = 3
+    for scale in [1e-3, 1e-5]:
+        for _ in range(10):
+            x = np.random.randn(sr * seconds) * scale
+            y = x + np.random.randn(sr * seconds) * scale
+
+            theirs_value = theirs(x, y, fs_sig=sr, extended=True)
+            ours_value = ours(x, y, fs_sig=sr, extended=True)
+            assert abs(theirs_value - ours_value) < 1e-6
+
+
+def test_bands():
+    np.random.seed(42)
+    sr = 16_000
+    seconds = 3
+    for extended in [False, True]:
+        x = np.random.randn(sr * seconds)
+        y = np.random.randn(sr * seconds)
+        bands = stoi_bands(x, y, fs_sig=sr, extended=extended)
+
+        assert bands.shape == band_center_freqs().shape == (15,)
+        assert abs(bands.mean() - ours(x, y, fs_sig=sr, extended=extended)) < 1e-6
+
+
+def test_scorer():
+    np.random.seed(42)
+    sr = 16_000
+    seconds = 3
+    for extended in [False, True]:
+        scorer = Stoi(fs=sr, extended=extended)
+        for _ in range(5):
+            x = np.random.randn(sr * seconds)
+            y = np.random.randn(sr * seconds)
+            assert scorer.score(x, y) == ours(x, y, fs_sig=sr, extended=extended)
+
+
+def test_batch():
+    # Rows of 2D arrays are scored in parallel through ndarray, as 1D arrays
+    np.random.seed(42)
+    sr = 16_000
+    seconds = 3
+    for extended in [False, True]:
+        x = np.random.randn(8, sr * seconds)
+        y = x + np.random.randn(8, sr * seconds)
+        batch = ours(x, y, fs_sig=sr, extended=extended)
+
+        assert batch.shape == (8,)
+        for x_row, y_row, value in zip(x, y, batch):
+            assert value == ours(x_row, y_row, fs_sig=sr, extended=extended)
+
+
+def test_silent_frames():
+    np.random.seed(42)
+    sr = 16_000
+    seconds = 3
+    for extended in [False, True]:
+        for _ in range(20):
+            x = np.random.randn(sr * seconds)
+            # Insert silent gaps so that non-adjacent frames are overlap-added
+            x[sr // 2 : sr] *= 1e-4
+            x[2 * sr : 2 * sr + sr // 10] *= 1e-4
+            y = x + np.random.randn(sr * seconds)
+
+            theirs_value = theirs(x, y, fs_sig=sr, extended=extended)
+            ours_value = ours(x, y, fs_sig=sr, extended=extended)
+            assert abs(theirs_value - ours_value) < 1e-6
+
+
+def test_empty():
+    x = np.zeros(0, dtype=np.float32)
+    with pytest.warns(UserWarning):
+        assert ours(x, x, fs_sig=16_000) == 1e-5
+
+
+def test_pathological():
+    np.random.seed(42)
+    sr = 16_000
+    signals = [
+        np.zeros(1),
+        np.zeros(100),
+        np.zeros(sr * 3),
+        np.full(sr * 3, np.nan),
+    ]
+    for x in signals:
+        y = np.random.randn(len(x))
+        for extended in [False, True]:
+            with pytest.warns(UserWarning):
+                assert ours(x, y, fs_sig=sr, extended=extended) == 1e-5
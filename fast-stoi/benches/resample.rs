@@ -0,0 +1,53 @@
+//! Resampling benchmarks: `cargo bench --features bench`
+//!
+//! Compares the slice and faer dot products of the polyphase filters, whose
+//! cross-over sets `FAER_DOT_MIN_LENGTH`, and times resampling 60s signals
+//! to 10kHz from the usual sampling frequencies.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use std::hint::black_box;
+
+use common::noise;
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use fast_stoi::{
+    FS, ResampleQuality,
+    bench::{faer_dot, slice_dot},
+    resample_polyphase,
+};
+
+/// Phase lengths of the reference filters from 8kHz, 22.05kHz and 48kHz,
+/// and around the cross-over
+const PHASE_LENGTHS: [usize; 6] = [73, 160, 349, 640, 768, 1024];
+
+fn dot_products(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dot");
+    for len in PHASE_LENGTHS {
+        let (a, b) = (noise(len, 1), noise(len, 2));
+        group.bench_with_input(BenchmarkId::new("slice", len), &len, |bench, _| {
+            bench.iter(|| slice_dot(black_box(&a), black_box(&b)))
+        });
+        group.bench_with_input(BenchmarkId::new("faer", len), &len, |bench, _| {
+            bench.iter(|| faer_dot(black_box(&a), black_box(&b)))
+        });
+    }
+    group.finish();
+}
+
+fn resampling(c: &mut Criterion) {
+    let mut group = c.benchmark_group("resample 60s to 10kHz");
+    group.sample_size(10);
+    for fs in [8_000, 22_050, 48_000] {
+        let x = noise(60 * fs, 3);
+        // Generate and cache the filter outside of the measurements
+        resample_polyphase(&x[..fs], fs, FS, ResampleQuality::Reference);
+        group.bench_with_input(BenchmarkId::from_parameter(fs), &fs, |bench, &fs| {
+            bench.iter(|| resample_polyphase(black_box(&x), fs, FS, ResampleQuality::Reference))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, dot_products, resampling);
+criterion_main!(benches);
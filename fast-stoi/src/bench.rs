@@ -0,0 +1,5 @@
+//! Internals exposed to the benchmarks, for benchmarking only.
+//!
+//! Nothing here is part of the public API: it may change in any release.
+
+pub use crate::upfirdn::{faer_dot, slice_dot};
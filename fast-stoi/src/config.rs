@@ -0,0 +1,453 @@
+//! STOI computation parameters
+
+use crate::{
+    constants::{BETA, FRAME_LENGTH, HOP_LENGTH, MAX_SIGNAL_LEN, NUM_BANDS, SEGMENT_LENGTH},
+    errors::{Result, StoiError},
+    resample::{ResampleMethod, ResamplePadding, ResampleQuality},
+};
+
+/// How NaN segment correlations are handled when averaging them into a score.
+/// NaN correlations arise from degenerate inputs, such as NaN samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum NanPolicy {
+    /// Ignore NaN correlations (like `np.nanmean`), logging a warning
+    #[default]
+    Skip,
+    /// Let NaN correlations turn the score into NaN, as the reference does
+    Propagate,
+}
+
+/// Frequency bands the spectrograms are merged into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BandScheme {
+    /// 15 one-third octave bands from 150Hz, as the reference
+    #[default]
+    ThirdOctave,
+    /// `n_mels` triangular mel-spaced bands over the same frequency range.
+    /// This is a research variant: its scores are not comparable with standard STOI.
+    Mel { n_mels: usize },
+}
+
+impl BandScheme {
+    /// Amount of bands in the scheme
+    pub fn num_bands(&self) -> usize {
+        match self {
+            BandScheme::ThirdOctave => NUM_BANDS,
+            BandScheme::Mel { n_mels } => *n_mels,
+        }
+    }
+}
+
+/// Correlation coefficient between the clean and processed band energies
+/// of each segment band, in the standard measure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CorrelationKind {
+    /// Pearson correlation, as the reference
+    #[default]
+    Pearson,
+    /// Spearman rank correlation: the Pearson correlation of the ranks of the
+    /// band energies within each segment band, after clipping. This is a
+    /// research variant, less sensitive to outliers: its scores are not
+    /// comparable with standard STOI.
+    Spearman,
+}
+
+/// How the trailing samples of a signal that no complete frame covers are handled.
+///
+/// Frames start every `hop_length` samples and stop before the end of the signal,
+/// as the reference: up to `hop_length` trailing samples are in no frame.
+///
+/// As the reference discards the last valid frame, a padded last frame is itself
+/// discarded when kept as speech, but brings the frame before it into the score.
+/// Policies differ in the padded frame energy: zeros lower it, so that a padded
+/// frame is more often silent, while reflection preserves the signal energy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PartialPolicy {
+    /// Ignore the trailing samples, as the reference
+    #[default]
+    Drop,
+    /// Complete the trailing samples into a last frame with zeros
+    ZeroPad,
+    /// Complete the trailing samples into a last frame by reflecting the signal
+    /// about its last sample, as `np.pad(x, mode="reflect")`
+    Reflect,
+}
+
+/// Window applied to the frames, both when removing silent frames and before
+/// the spectrograms. All windows have `256 + 2` samples trimmed of their zero
+/// endpoints, as the reference hann window.
+///
+/// STOI is invariant to the window gain: spectrograms are not rescaled, as
+/// correlations and the clipping of the standard measure are scale-invariant.
+/// Silent frames thresholds relative to the maximum frame energy are too,
+/// unlike `vad_reference_db` without `vad_normalize_window`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FrameWindow {
+    /// Hann window, as the reference (coherent gain of 0.5)
+    #[default]
+    Hann,
+    /// Triangular window (coherent gain of 0.5)
+    Bartlett,
+    /// Parabolic window (coherent gain of 2/3)
+    Welch,
+}
+
+/// Parameters of the STOI computation.
+///
+/// The default configuration reproduces the reference STOI implementation,
+/// as [`StoiConfig::reference`] and [`StoiConfig::reference_extended`].
+/// Any other value is non-standard, and the resulting scores are not
+/// comparable with the reference.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoiConfig {
+    /// Whether to use the extended STOI measure
+    pub extended: bool,
+    /// Amount of frames in a short-time analysis segment, at least 2:
+    /// correlations of a single frame are degenerate.
+    /// Defaults to 30 frames (~384ms at 10kHz).
+    pub segment_frames: usize,
+    /// Amount of samples between the starts of consecutive frames, in `1..=256`.
+    /// Defaults to 128 (half overlap). Smaller hops yield more frames, which
+    /// both increases the compute cost and shortens the segments duration.
+    pub hop_length: usize,
+    /// Optional gap in dB between the thresholds entering and exiting speech
+    /// in silent frame removal. Frames inside speech are only discarded when
+    /// their energy falls more than this gap below the usual threshold.
+    /// Defaults to `None` (single threshold).
+    pub vad_hysteresis: Option<f32>,
+    /// Optional absolute energy in dB replacing the maximum frame energy of the
+    /// clean signal as the reference of silent frame removal: frames more than
+    /// 40dB below it are silent. Frame energies are `20 * log10` of the L2 norm
+    /// of windowed frames of 256 samples at 10kHz, about 17dB for a full scale
+    /// sine with the hann window. A fixed reference gates silence consistently
+    /// across signals of different loudness, but quiet signals may then lose
+    /// most of their frames, and loud noise is kept as speech. Defaults to `None`
+    /// (relative to the maximum frame energy), as the reference implementation.
+    pub vad_reference_db: Option<f32>,
+    /// Whether `vad_reference_db` is a raw frame energy rather than a windowed one.
+    /// Windowed energies are normalized by the window energy `sum(w²)` (96.4 for
+    /// the hann window, or 19.8dB), so that a full scale sine has an energy of
+    /// about -3dB instead of 17dB. Without `vad_reference_db`, this has no effect:
+    /// thresholds relative to the maximum energy are invariant to this constant
+    /// normalization. Defaults to `false` (windowed energies), as the reference.
+    pub vad_normalize_window: bool,
+    /// Window applied to the frames. Defaults to hann, as the reference.
+    /// This is a research variant: other windows give scores not comparable
+    /// with standard STOI.
+    pub frame_window: FrameWindow,
+    /// How NaN segment correlations are averaged. Defaults to skipping them.
+    pub nan_policy: NanPolicy,
+    /// Algorithm used to resample signals to 10kHz. Defaults to polyphase
+    /// filtering, as the reference implementation.
+    pub resample_method: ResampleMethod,
+    /// Filter design of polyphase resampling. Defaults to the reference design.
+    pub resample_quality: ResampleQuality,
+    /// Extension of the signal edges in polyphase resampling, ignored by FFT
+    /// resampling. Defaults to zeros, as the reference implementation.
+    pub resample_padding: ResamplePadding,
+    /// Frequency bands used to merge the spectrograms.
+    /// Defaults to third octave bands, as the reference implementation.
+    pub band_scheme: BandScheme,
+    /// Whether to clamp the final score to `[-1, 1]`, which floating point errors
+    /// may marginally exceed. The reference implementation does not clamp.
+    /// Defaults to `false`.
+    pub clamp_score: bool,
+    /// Whether to skip the computation for identical signals, returning exactly 1.
+    /// Signals are identical when all their samples compare equal, so that
+    /// signals containing NaN never are. Silent frames are still removed,
+    /// and errors are the same as the full computation. The full computation
+    /// yields 1 up to rounding errors (~1e-6 with the extended measure).
+    /// Defaults to `false`.
+    pub identical_shortcut: bool,
+    /// Whether to trim the leading and trailing silence of both signals
+    /// at 10kHz before framing. Silence is detected on the clean signal with
+    /// the silent frames dynamic range: samples more than 40dB below
+    /// its peak are silent. Both signals are trimmed by the same range.
+    /// Silence padding is removed exactly at 10kHz, while at other rates it
+    /// shifts the resampling phase and still changes scores slightly.
+    /// Defaults to `false`.
+    pub trim_silence: bool,
+    /// Whether to scale both signals at 10kHz to an RMS of 0.1 (-20dBFS) before
+    /// framing, after trimming silence. Each signal is scaled separately, and
+    /// silent signals are left unchanged. Scores are invariant to the gains of
+    /// both signals with the default parameters: this only changes scores with
+    /// an absolute `vad_reference_db`, where loud and quiet recordings of the
+    /// same speech would otherwise keep different frames. Defaults to `false`.
+    pub normalize_rms: bool,
+    /// Whether to return [`StoiError::Clipped`] for clipped signals, whose scores
+    /// are misleading. A signal is clipped when more than 0.1% of its samples
+    /// are at full scale (absolute value of at least 1). Defaults to `false`.
+    pub strict_clipping: bool,
+    /// Whether to return [`StoiError::InvalidSampleRate`] for sampling frequencies
+    /// outside of [`supported_sample_rates`](crate::supported_sample_rates), whose
+    /// scores are not validated against the reference. Otherwise, such rates only
+    /// log a warning. Defaults to `false`.
+    pub strict_sample_rate: bool,
+    /// Whether to pad signals with less than `segment_frames` valid frames
+    /// (plus the trimmed segments) up to a single segment instead of returning
+    /// [`StoiError::NotEnoughFrames`]. Padding happens after silent frame
+    /// removal: the band energies of the valid frames are reflected, so that
+    /// the padding is never considered as speech and is not counted in the
+    /// valid frames. Scores of padded signals have a high variance and are not
+    /// comparable with standard STOI. Signals need at least one valid frame.
+    /// Defaults to `false`.
+    pub pad_short: bool,
+    /// How trailing samples in no complete frame are handled. Padding policies
+    /// add one frame when there are such samples, so that every sample is in
+    /// a frame (`1 + ceil((len - 256) / hop_length)` frames instead of
+    /// `1 + (len - 257) / hop_length`). The padded frame is removed when silent,
+    /// as any other frame. Defaults to `Drop`, as the reference.
+    pub include_partial: PartialPolicy,
+    /// Optional floor in dB (at most 0) of band energies relative to the
+    /// maximum band energy of each segment. Every band energy of a segment
+    /// is raised to at least `max * 10^(floor_db / 20)`, which stabilizes the
+    /// correlations of near-silent bands. Both signals are floored separately,
+    /// before the standard measure clips the processed signal relative to the
+    /// floored clean signal. Defaults to `None` (no floor), as the reference.
+    pub band_floor_db: Option<f32>,
+    /// Lower bound in dB of the signal-to-distortion ratio of the standard measure:
+    /// processed band energies are clipped to at most `1 + 10^(-clip_db / 20)`
+    /// times the clean band energies after normalization. Lower values clip
+    /// less, so that strong distortions lower the scores more. Any value other
+    /// than -15dB is non-standard, and the extended measure ignores it.
+    /// Defaults to -15dB, as the reference implementation.
+    pub clip_db: f32,
+    /// Correlation coefficient of the standard measure. The extended measure
+    /// has its own normalization and only supports Pearson correlations.
+    /// Defaults to Pearson, as the reference.
+    pub correlation: CorrelationKind,
+    /// Amount of segments excluded from the score at both the start and the end
+    /// of the valid frames, whose band energies may hold framing or resampling
+    /// transients. Signals need `segment_frames + 2 * trim_segments` valid frames
+    /// to keep a segment, and short signals are padded up to this amount with
+    /// `pad_short`. Trimmed segments are also excluded from the band scores and
+    /// the correlation matrix. This is a research variant: any value other
+    /// than 0 gives scores not comparable with standard STOI. Defaults to 0.
+    pub trim_segments: usize,
+    /// Whether to weight the band scores by the clean signal energy of each band
+    /// in the final average, so that louder bands contribute more. This is a
+    /// research variant: its scores are not comparable with standard STOI.
+    /// With the extended measure, the weighted band scores are the band
+    /// contributions to the segment correlations. Defaults to `false`.
+    pub weight_bands: bool,
+    /// Optional maximum length of the signals in samples after resampling to 10kHz.
+    /// Longer signals return [`StoiError::SignalTooLong`] before any allocation,
+    /// as their computation would need a huge amount of memory. Defaults to
+    /// `Some(36_000_000)` (1 hour at 10kHz, about 1.7GB of memory).
+    /// `None` only keeps the limit of addressable buffers, `isize::MAX / 4`
+    /// samples (2^29, about 15 hours at 10kHz, on 32-bit targets).
+    pub max_signal_len: Option<usize>,
+}
+
+impl Default for StoiConfig {
+    fn default() -> Self {
+        Self::reference()
+    }
+}
+
+impl StoiConfig {
+    /// Parameters of the reference STOI implementation, with the standard measure.
+    ///
+    /// Only this configuration and [`StoiConfig::reference_extended`] yield scores
+    /// comparable with the reference implementation and the literature.
+    pub fn reference() -> Self {
+        Self {
+            extended: false,
+            segment_frames: SEGMENT_LENGTH,
+            hop_length: HOP_LENGTH,
+            vad_hysteresis: None,
+            vad_reference_db: None,
+            vad_normalize_window: false,
+            frame_window: FrameWindow::Hann,
+            nan_policy: NanPolicy::Skip,
+            resample_method: ResampleMethod::Polyphase,
+            resample_quality: ResampleQuality::Reference,
+            resample_padding: ResamplePadding::Zero,
+            band_scheme: BandScheme::ThirdOctave,
+            clamp_score: false,
+            identical_shortcut: false,
+            trim_silence: false,
+            normalize_rms: false,
+            strict_clipping: false,
+            strict_sample_rate: false,
+            pad_short: false,
+            include_partial: PartialPolicy::Drop,
+            band_floor_db: None,
+            clip_db: BETA,
+            correlation: CorrelationKind::Pearson,
+            trim_segments: 0,
+            weight_bands: false,
+            max_signal_len: Some(MAX_SIGNAL_LEN),
+        }
+    }
+
+    /// Parameters of the reference STOI implementation, with the extended measure.
+    pub fn reference_extended() -> Self {
+        Self {
+            extended: true,
+            ..Self::reference()
+        }
+    }
+
+    /// Whether the parameters are one of the reference configurations,
+    /// whose scores are comparable with the reference implementation.
+    pub fn is_reference(&self) -> bool {
+        *self == Self::reference() || *self == Self::reference_extended()
+    }
+
+    /// Minimum amount of valid frames to compute a score without padding:
+    /// a segment, along with the trimmed segments before and after it.
+    /// Saturates to `usize::MAX` for amounts of trimmed segments that
+    /// [`StoiConfig::validate`] rejects.
+    pub fn min_valid_frames(&self) -> usize {
+        self.checked_min_valid_frames().unwrap_or(usize::MAX)
+    }
+
+    fn checked_min_valid_frames(&self) -> Option<usize> {
+        self.trim_segments
+            .checked_mul(2)?
+            .checked_add(self.segment_frames)
+    }
+
+    /// Check that the parameters can be used for a STOI computation.
+    pub fn validate(&self) -> Result<()> {
+        if self.segment_frames < 2 {
+            return Err(StoiError::InvalidConfig(
+                "segment_frames must be at least 2",
+            ));
+        }
+
+        if self.checked_min_valid_frames().is_none() {
+            return Err(StoiError::InvalidConfig(
+                "trim_segments overflows the amount of valid frames",
+            ));
+        }
+
+        if !(1..=FRAME_LENGTH).contains(&self.hop_length) {
+            return Err(StoiError::InvalidConfig(
+                "hop_length must be between 1 and the frame length",
+            ));
+        }
+
+        if self
+            .vad_hysteresis
+            .is_some_and(|gap| gap.is_nan() || gap < 0.0)
+        {
+            return Err(StoiError::InvalidConfig("vad_hysteresis must be positive"));
+        }
+
+        if self
+            .vad_reference_db
+            .is_some_and(|reference| !reference.is_finite())
+        {
+            return Err(StoiError::InvalidConfig("vad_reference_db must be finite"));
+        }
+
+        if let ResampleQuality::Custom { half_length, beta } = self.resample_quality {
+            if half_length == 0 {
+                return Err(StoiError::InvalidConfig(
+                    "resample half_length must be strictly positive",
+                ));
+            }
+            if !beta.is_finite() || beta < 0.0 {
+                return Err(StoiError::InvalidConfig(
+                    "resample beta must be finite and positive",
+                ));
+            }
+        }
+
+        if self
+            .band_floor_db
+            .is_some_and(|floor| floor.is_nan() || floor > 0.0)
+        {
+            return Err(StoiError::InvalidConfig("band_floor_db must be negative"));
+        }
+
+        if !self.clip_db.is_finite() {
+            return Err(StoiError::InvalidConfig("clip_db must be finite"));
+        }
+
+        if self.extended && self.correlation != CorrelationKind::Pearson {
+            return Err(StoiError::InvalidConfig(
+                "the extended measure only supports Pearson correlations",
+            ));
+        }
+
+        if self.band_scheme.num_bands() == 0 {
+            return Err(StoiError::InvalidConfig("n_mels must be strictly positive"));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_resample_betas_must_be_finite_and_positive() {
+        for beta in [f32::NAN, f32::INFINITY, -1.0] {
+            let config = StoiConfig {
+                resample_quality: ResampleQuality::Custom {
+                    half_length: 10,
+                    beta,
+                },
+                ..StoiConfig::default()
+            };
+            assert!(matches!(
+                config.validate(),
+                Err(StoiError::InvalidConfig(_))
+            ));
+        }
+
+        let config = StoiConfig {
+            resample_quality: ResampleQuality::Custom {
+                half_length: 10,
+                beta: 0.0,
+            },
+            ..StoiConfig::default()
+        };
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn segments_need_at_least_two_frames() {
+        for segment_frames in [0, 1] {
+            let config = StoiConfig {
+                segment_frames,
+                ..StoiConfig::default()
+            };
+            assert!(matches!(
+                config.validate(),
+                Err(StoiError::InvalidConfig(_))
+            ));
+        }
+
+        let config = StoiConfig {
+            segment_frames: 2,
+            ..StoiConfig::default()
+        };
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn overflowing_trimmed_segments_are_rejected() {
+        let config = StoiConfig {
+            trim_segments: usize::MAX / 2,
+            ..StoiConfig::default()
+        };
+        assert_eq!(config.min_valid_frames(), usize::MAX);
+        assert!(matches!(
+            config.validate(),
+            Err(StoiError::InvalidConfig(_))
+        ));
+
+        let config = StoiConfig {
+            trim_segments: 3,
+            ..StoiConfig::default()
+        };
+        assert_eq!(config.min_valid_frames(), 36);
+        assert_eq!(config.validate(), Ok(()));
+    }
+}
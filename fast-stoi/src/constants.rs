@@ -9,7 +9,7 @@ pub const HALF_FRAME: usize = FRAME_LENGTH / 2;
 // Audio frame hop length (half overlap)
 pub const HOP_LENGTH: usize = FRAME_LENGTH / 2;
 
-// Internal sampling frequency for STOI computation
+/// Internal sampling frequency of the STOI computation, which signals are resampled to
 pub const FS: usize = 10_000;
 
 pub const FFT_LENGTH: usize = 512;
@@ -20,3 +20,16 @@ pub const NUM_BANDS: usize = 15; // Amount of 13 octave band
 pub const SEGMENT_LENGTH: usize = 30;
 
 pub const BETA: f32 = -15.0; // Lower SDR bound
+
+// Machine epsilon of the float64 reference (np.finfo(float).eps)
+pub const REFERENCE_EPS: f32 = f64::EPSILON as f32;
+
+pub const NORMALIZED_RMS: f32 = 0.1; // Target RMS of level normalization (-20dBFS)
+
+pub const MAX_SIGNAL_LEN: usize = 36_000_000; // Default max length at FS (1 hour)
+
+// Max length of a f32 buffer, as allocations are limited to isize::MAX bytes
+// (2^29 samples, about 15 hours at FS, on 32-bit targets)
+pub const MAX_BUFFER_LEN: usize = isize::MAX as usize / size_of::<f32>();
+
+pub const CLIPPING_RATIO: f32 = 0.001; // Max ratio of full scale samples in strict mode
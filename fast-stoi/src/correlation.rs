@@ -0,0 +1,117 @@
+//! Correlation between band vectors
+
+/// Pearson correlation coefficient between two vectors of the same length.
+///
+/// Means are subtracted explicitly before computing the norms (two-pass),
+/// which avoids the cancellation errors of single-pass formulas.
+///
+/// As in the STOI computation, `f32::EPSILON` is added to both norms, so that
+/// a constant (zero-variance) input yields a correlation of 0 instead of NaN.
+/// Consequently, correlations of inputs with tiny variances are slightly
+/// shrunk towards 0.
+///
+/// Vectors of different lengths, or empty vectors, have no correlation:
+/// they yield NaN, as NaN segment correlations of the STOI computation.
+pub fn pearson(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return f32::NAN;
+    }
+
+    let len = a.len() as f32;
+    let a_mean = a.iter().sum::<f32>() / len;
+    let b_mean = b.iter().sum::<f32>() / len;
+
+    let mut dot = 0.0;
+    let mut a_sq_sum = 0.0;
+    let mut b_sq_sum = 0.0;
+    a.iter().zip(b.iter()).for_each(|(a, b)| {
+        let a = a - a_mean;
+        let b = b - b_mean;
+        dot += a * b;
+        a_sq_sum += a * a;
+        b_sq_sum += b * b;
+    });
+
+    let a_norm = a_sq_sum.sqrt() + f32::EPSILON;
+    let b_norm = b_sq_sum.sqrt() + f32::EPSILON;
+
+    dot / (a_norm * b_norm)
+}
+
+/// Spearman rank correlation coefficient between two vectors of the same length.
+///
+/// This is the [`pearson`] correlation of the ranks of both vectors, where tied
+/// values share the average of their ranks. Ranks only depend on the order of
+/// the values: monotonic relationships have a correlation of 1, and outliers
+/// weigh no more than any other value.
+///
+/// As with [`pearson`], vectors of different lengths or empty vectors yield NaN.
+pub fn spearman(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return f32::NAN;
+    }
+
+    let mut order = Vec::with_capacity(a.len());
+    let mut a_ranks = a.to_vec();
+    let mut b_ranks = b.to_vec();
+    rank(&mut a_ranks, &mut order);
+    rank(&mut b_ranks, &mut order);
+
+    pearson(&a_ranks, &b_ranks)
+}
+
+/// Replace values by their ranks from 1, tied values sharing the average of their ranks.
+/// `order` is a scratch buffer, reused across calls to avoid allocations.
+pub fn rank(values: &mut [f32], order: &mut Vec<usize>) {
+    order.clear();
+    order.extend(0..values.len());
+    order.sort_unstable_by(|&i, &j| values[i].total_cmp(&values[j]));
+
+    let mut start = 0;
+    while start < order.len() {
+        // Tied values are consecutive in the sorted order (NaN ties with nothing)
+        let value = values[order[start]];
+        let end = start + 1 + order[start + 1..].partition_point(|&i| values[i] == value);
+        let average_rank = (start + end + 1) as f32 / 2.0;
+
+        for &i in &order[start..end] {
+            values[i] = average_rank;
+        }
+        start = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pearson_matches_hand_computed_correlations() {
+        let a = [1.0, 2.0, 3.0, 4.0];
+
+        // Deviations (-1.5, -0.5, 0.5, 1.5) and (-3, -1, 0, 4): 11 / sqrt(5 * 26)
+        let r = pearson(&a, &[2.0, 4.0, 5.0, 9.0]);
+        assert!((r - 11.0 / 130.0_f32.sqrt()).abs() < 1e-6, "{r}");
+
+        assert!((pearson(&a, &[3.0, 5.0, 7.0, 9.0]) - 1.0).abs() < 1e-6);
+        assert!((pearson(&a, &[-1.0, -2.0, -3.0, -4.0]) + 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn pearson_of_constant_inputs_is_zero() {
+        assert_eq!(pearson(&[1.0, 2.0, 3.0], &[5.0; 3]), 0.0);
+        assert_eq!(pearson(&[5.0; 3], &[5.0; 3]), 0.0);
+    }
+
+    #[test]
+    fn pearson_of_mismatched_or_empty_inputs_is_nan() {
+        assert!(pearson(&[1.0, 2.0], &[1.0]).is_nan());
+        assert!(pearson(&[], &[]).is_nan());
+    }
+
+    #[test]
+    fn spearman_of_mismatched_or_empty_inputs_is_nan() {
+        assert!(spearman(&[1.0, 2.0], &[1.0]).is_nan());
+        assert!(spearman(&[], &[]).is_nan());
+    }
+}
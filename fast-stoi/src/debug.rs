@@ -0,0 +1,233 @@
+//! Debugging utilities exposing the internals of the STOI computation.
+//!
+//! These functions copy intermediate results that are otherwise internal, and are only
+//! meant to inspect what the metric actually computes.
+
+use faer::prelude::*;
+
+use crate::{
+    config::StoiConfig,
+    constants::{FFT_BINS, FRAME_LENGTH, FS, HOP_LENGTH},
+    errors::Result,
+    frames, octave, pipeline, stft,
+};
+
+/// Compute the STOI score along with the clean and processed signals
+/// after resampling to the internal 10kHz sampling frequency.
+///
+/// Args:
+/// * `x` - Clean speech signal
+/// * `y` - Processed speech signal
+/// * `fs_sig` - Sampling frequency of the signals
+/// * `config` - Computation parameters
+pub fn stoi_debug(
+    x: &[f32],
+    y: &[f32],
+    fs_sig: usize,
+    config: &StoiConfig,
+) -> Result<(f32, Vec<f32>, Vec<f32>)> {
+    pipeline::check_inputs(x, y, fs_sig, config)?;
+
+    let x = pipeline::to_internal_rate(x, fs_sig, config).into_owned();
+    let y = pipeline::to_internal_rate(y, fs_sig, config).into_owned();
+    let score = pipeline::compute(&x, &y, config)?.score();
+
+    Ok((score, x, y))
+}
+
+/// Trace the silent frame removal on a clean signal, after resampling to 10kHz.
+///
+/// Returns for every frame whether it is kept as speech, along with its energy in dB
+/// relative to the speech threshold, 40dB below the most energetic frame
+/// (or below `config.vad_reference_db`).
+/// This shows how far each frame is from the cutoff. With `config.vad_hysteresis`,
+/// frames within speech are kept down to the hysteresis gap below the threshold.
+/// Note that the full computation additionally discards the last kept frame.
+///
+/// Args:
+/// * `x` - Clean speech signal
+/// * `fs_sig` - Sampling frequency of the signal
+/// * `config` - Computation parameters
+pub fn vad_trace(x: &[f32], fs_sig: usize, config: &StoiConfig) -> Result<Vec<(bool, f32)>> {
+    pipeline::check_sample_rate(fs_sig, config)?;
+    config.validate()?;
+
+    let x = pipeline::to_internal_rate(x, fs_sig, config);
+
+    Ok(frames::vad_trace(&x, config))
+}
+
+/// Compute the phase differences between the STFTs of two signals,
+/// after resampling to the internal 10kHz sampling frequency.
+///
+/// This is a diagnostic, not part of STOI: the STOI computation only uses the
+/// spectrogram magnitudes, and discards their phases. Frames of 256 samples are
+/// taken every 128 samples with a hann window, without removing silent frames.
+///
+/// Returns the phase of the processed spectrum minus the phase of the clean
+/// spectrum in radians, wrapped to `[-pi, pi]`, with shape (257, frames).
+/// Identical signals have zero phase differences.
+///
+/// Args:
+/// * `x` - Clean speech signal
+/// * `y` - Processed speech signal
+/// * `fs_sig` - Sampling frequency of the signals
+pub fn spectral_phase_difference(x: &[f32], y: &[f32], fs_sig: usize) -> Result<Mat<f32>> {
+    let config = StoiConfig::default();
+    pipeline::check_inputs(x, y, fs_sig, &config)?;
+
+    let x = pipeline::to_internal_rate(x, fs_sig, &config);
+    let y = pipeline::to_internal_rate(y, fs_sig, &config);
+
+    let window = frames::hann(FRAME_LENGTH);
+    let n_frames = frames::frame_count(x.len(), HOP_LENGTH);
+    let read_frame = |signal: &[f32], k: usize, out: &mut [f32]| {
+        let start = k * HOP_LENGTH;
+        out.iter_mut()
+            .zip(&signal[start..start + FRAME_LENGTH])
+            .zip(window.iter())
+            .for_each(|((out, sample), w)| *out = sample * w);
+    };
+
+    // Clean spectra are stored to be compared with the processed spectra
+    let mut x_spectra = Vec::with_capacity(FFT_BINS * n_frames);
+    stft::for_each_frame_rfft(
+        n_frames,
+        FRAME_LENGTH,
+        |k, out| read_frame(&x, k, out),
+        |_, spectrum| x_spectra.extend_from_slice(spectrum),
+    );
+
+    let mut phases = Mat::<f32>::zeros(FFT_BINS, n_frames);
+    stft::for_each_frame_rfft(
+        n_frames,
+        FRAME_LENGTH,
+        |k, out| read_frame(&y, k, out),
+        |k, spectrum| {
+            let x_spectrum = &x_spectra[k * FFT_BINS..(k + 1) * FFT_BINS];
+            phases
+                .col_mut(k)
+                .iter_mut()
+                .zip(spectrum.iter().zip(x_spectrum))
+                .for_each(|(phase, (y, x))| *phase = (y * x.conj()).arg());
+        },
+    );
+
+    Ok(phases)
+}
+
+/// Compute the one-third octave band energies of the residual signal
+/// (processed minus clean), after resampling to the internal 10kHz sampling frequency.
+///
+/// This is a diagnostic, not part of STOI: it shows in which bands a processing adds
+/// errors, such as denoising artifacts. Both signals are resampled, then truncated
+/// to the length of the shortest one before their difference is taken. The residual
+/// goes through the same stages as a processed signal: silent frames are
+/// removed based on the clean signal, and the remaining frames are overlap-added
+/// and windowed before the spectrograms.
+///
+/// Returns the band energies with shape (15, frames), which are all zero
+/// for identical signals.
+///
+/// Args:
+/// * `x` - Clean speech signal
+/// * `y` - Processed speech signal
+/// * `fs_sig` - Sampling frequency of the signals
+pub fn residual_band_energy(x: &[f32], y: &[f32], fs_sig: usize) -> Result<Mat<f32>> {
+    let config = StoiConfig::default();
+    pipeline::check_sample_rate(fs_sig, &config)?;
+    pipeline::check_length(x.len().max(y.len()), fs_sig, &config)?;
+
+    let x = pipeline::to_internal_rate(x, fs_sig, &config);
+    let y = pipeline::to_internal_rate(y, fs_sig, &config);
+
+    let len = x.len().min(y.len());
+    let x = &x[..len];
+    let residual: Vec<f32> = y[..len].iter().zip(x).map(|(y, x)| y - x).collect();
+
+    if len <= FRAME_LENGTH {
+        return Err(pipeline::too_short(len, FS, &config));
+    }
+
+    let windows = frames::frame_windows(config.frame_window);
+    let frames = frames::process_frames(x, &residual[..], &config, windows)?;
+    let spectrogram =
+        stft::compute_frame_rffts(frames.count, FRAME_LENGTH, |k, out| frames.read_y(k, out));
+
+    Ok(octave::compute_octave_bands(spectrogram.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::noise;
+
+    #[test]
+    fn vad_trace_splits_speech_from_silence() {
+        // 1s of speech-like noise followed by 1s of faint noise, 80dB lower
+        let mut x = noise(20_000, 1);
+        x[10_000..].iter_mut().for_each(|sample| *sample *= 1e-4);
+
+        let trace = vad_trace(&x, FS, &StoiConfig::default()).unwrap();
+        assert_eq!(trace.len(), frames::frame_count(x.len(), HOP_LENGTH));
+
+        // Frames ending before the boundary are speech, those starting after it are silent
+        let last_speech = (10_000 - FRAME_LENGTH) / HOP_LENGTH;
+        let first_silent = 10_000usize.div_ceil(HOP_LENGTH);
+        for (i, &(kept, relative_db)) in trace.iter().enumerate() {
+            if i <= last_speech {
+                assert!(kept && relative_db > 30.0, "frame {i}: {relative_db}");
+            } else if i >= first_silent {
+                assert!(!kept && relative_db < -30.0, "frame {i}: {relative_db}");
+            }
+        }
+    }
+
+    #[test]
+    fn identical_signals_have_zero_phase_differences() {
+        let x = noise(16_000, 2);
+        let phases = spectral_phase_difference(&x, &x, 16_000).unwrap();
+        assert_eq!(phases.nrows(), FFT_BINS);
+        assert_eq!(phases.ncols(), frames::frame_count(10_000, HOP_LENGTH));
+        assert!(
+            phases
+                .col_iter()
+                .flat_map(|frame| frame.iter())
+                .all(|&phase| phase == 0.0)
+        );
+
+        // An inverted signal is out of phase in every bin
+        let inverted: Vec<f32> = x.iter().map(|sample| -sample).collect();
+        let phases = spectral_phase_difference(&x, &inverted, 16_000).unwrap();
+        assert!(
+            phases
+                .col_iter()
+                .flat_map(|frame| frame.iter())
+                .all(|&phase| (phase.abs() - std::f32::consts::PI).abs() < 1e-3)
+        );
+    }
+
+    #[test]
+    fn identical_signals_have_no_residual_energy() {
+        let x = noise(16_000, 3);
+        let bands = residual_band_energy(&x, &x, 16_000).unwrap();
+        assert_eq!(bands.nrows(), 15);
+        assert!(bands.ncols() > 0);
+        assert!(
+            bands
+                .col_iter()
+                .flat_map(|frame| frame.iter())
+                .all(|&energy| energy == 0.0)
+        );
+
+        // Added noise remains in the residual
+        let y = crate::test_signals::noisy(&x, 0.1, 4);
+        let bands = residual_band_energy(&x, &y, 16_000).unwrap();
+        assert!(
+            bands
+                .col_iter()
+                .flat_map(|frame| frame.iter())
+                .all(|&energy| energy > 0.0)
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! Reusable scorer for worker threads
+
+use crate::{config::StoiConfig, constants::FS, errors::Result, pipeline};
+
+/// Reusable STOI scorer for a fixed sampling frequency and configuration.
+///
+/// An engine is cheap to clone and is `Send + Sync`, so that every worker
+/// thread of a pool can hold its own clone, or share one by reference.
+/// Engines hold no state of their own: the resampling filters and FFT plans
+/// are cached process-wide, and shared by all engines and free functions.
+/// Creating an engine generates the resampling filter of its sampling
+/// frequency ahead of the first score.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoiEngine {
+    fs_sig: usize,
+    config: StoiConfig,
+}
+
+// Engines are meant to be moved and shared across threads
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<StoiEngine>();
+};
+
+impl StoiEngine {
+    /// Create an engine scoring signals sampled at `fs_sig` with `config`.
+    ///
+    /// Returns an error if the sampling frequency or the parameters are invalid,
+    /// as the scores would.
+    pub fn new(fs_sig: usize, config: StoiConfig) -> Result<Self> {
+        pipeline::check_sample_rate(fs_sig, &config)?;
+        config.validate()?;
+        pipeline::check_validated_rate(fs_sig, &config)?;
+
+        // Generate the resampling filter ahead of the first score
+        if fs_sig != FS {
+            pipeline::to_internal_rate(&[], fs_sig, &config);
+        }
+
+        Ok(Self { fs_sig, config })
+    }
+
+    /// Sampling frequency of the scored signals
+    pub fn fs_sig(&self) -> usize {
+        self.fs_sig
+    }
+
+    /// Parameters of the computation
+    pub fn config(&self) -> &StoiConfig {
+        &self.config
+    }
+
+    /// Compute the STOI measure between two signals, as [`stoi_with_config`](crate::stoi_with_config).
+    ///
+    /// Args:
+    /// * `x` - Clean speech signal
+    /// * `y` - Processed speech signal
+    pub fn score(&self, x: &[f32], y: &[f32]) -> Result<f32> {
+        crate::stoi_with_config(x, y, self.fs_sig, &self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{noise, noisy};
+
+    #[test]
+    fn cloned_engines_score_on_worker_threads() {
+        let engine = StoiEngine::new(16_000, StoiConfig::default()).unwrap();
+        let pairs: Vec<_> = (0..8)
+            .map(|seed| {
+                let x = noise(16_000, seed);
+                let y = noisy(&x, 1.0, seed + 100);
+                (x, y)
+            })
+            .collect();
+
+        let scores: Vec<f32> = std::thread::scope(|scope| {
+            let workers: Vec<_> = pairs
+                .iter()
+                .map(|(x, y)| {
+                    let engine = engine.clone();
+                    scope.spawn(move || engine.score(x, y).unwrap())
+                })
+                .collect();
+            workers.into_iter().map(|w| w.join().unwrap()).collect()
+        });
+
+        for ((x, y), score) in pairs.iter().zip(scores) {
+            assert_eq!(score, crate::stoi(x, y, 16_000, false).unwrap());
+        }
+    }
+
+    #[test]
+    fn engines_reject_invalid_rates() {
+        assert!(StoiEngine::new(0, StoiConfig::default()).is_err());
+        let strict = StoiConfig {
+            strict_sample_rate: true,
+            ..StoiConfig::default()
+        };
+        assert!(StoiEngine::new(12_345, strict).is_err());
+    }
+}
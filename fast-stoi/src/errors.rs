@@ -2,21 +2,250 @@
 
 use std::fmt::{self, Display, Formatter};
 
-#[derive(Debug, Clone)]
-pub struct NotEnoughFramesError;
+#[derive(Debug, Clone, PartialEq)]
+pub enum StoiError {
+    /// Not enough frames remain after removing silent frames
+    NotEnoughFrames {
+        /// Frames remaining after removing silent frames
+        valid_frames: usize,
+        /// Frames needed to compute a single segment, along with the trimmed segments
+        segment_frames: usize,
+    },
+    /// The computation parameters are invalid
+    InvalidConfig(&'static str),
+    /// The clean signal has no energy (only zero or NaN samples)
+    SilentSignal,
+    /// The signals are too short to yield a single segment
+    SignalTooShort {
+        /// Length of the signals in samples
+        len: usize,
+        /// Sampling frequency the length is measured at
+        fs: usize,
+        /// Minimum length to yield a single segment without silent frames
+        min_len: usize,
+    },
+    /// The signals are longer than the maximum length of the configuration
+    SignalTooLong {
+        /// Length of the signals in samples at 10kHz
+        len: usize,
+        /// Maximum length in samples at 10kHz
+        max_len: usize,
+    },
+    /// The clean and processed signals have different lengths
+    LengthMismatch {
+        /// Length of the clean signal
+        x_len: usize,
+        /// Length of the processed signal
+        y_len: usize,
+    },
+    /// The requested time region is outside of the signals
+    InvalidRegion,
+    /// The sampling frequency is zero, too high for its resampling filter to be
+    /// allocated, or not validated with `strict_sample_rate`
+    InvalidSampleRate(usize),
+    /// Too many samples of a signal are at full scale
+    Clipped {
+        /// Amount of full scale samples
+        count: usize,
+    },
+    /// A spectrogram has less frequency bins than a 512 samples FFT
+    SpectrumTooShort {
+        /// Frequency bins of the spectrogram
+        bins: usize,
+        /// Frequency bins of a 512 samples FFT
+        min_bins: usize,
+    },
+    /// The WAV data could not be read or decoded
+    #[cfg(feature = "wav")]
+    Wav(String),
+    /// The intermediate arrays could not be written
+    #[cfg(feature = "debug-npy")]
+    Npy(String),
+}
+
+pub type Result<T> = std::result::Result<T, StoiError>;
 
-pub type Result<T> = std::result::Result<T, NotEnoughFramesError>;
+/// Stage of the STOI computation where an error occurs, in pipeline order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    /// Validation of the signals and parameters, and WAV and NPY files
+    Input,
+    /// Resampling the signals to 10kHz
+    Resample,
+    /// Slicing the signals into frames and removing silent frames
+    Framing,
+    /// Spectrograms of the frames (never fails on its own)
+    Stft,
+    /// Merging spectrograms into one-third octave bands
+    Octave,
+    /// Correlations of the band segments (never fails on its own:
+    /// NaN correlations are handled according to the NaN policy)
+    Correlation,
+}
 
-const MESSAGE: &str = "Not enough STFT frames to compute intermediate \
+impl StoiError {
+    /// Stage of the computation that reports the error, to know which part
+    /// of the pipeline a signal fails in.
+    ///
+    /// Every error is reported by a single stage. Signals too short for a
+    /// single segment are reported by framing, even when rejected before
+    /// resampling, as a segment of frames is what they lack.
+    pub fn context(&self) -> Stage {
+        match self {
+            StoiError::InvalidConfig(_)
+            | StoiError::SignalTooLong { .. }
+            | StoiError::LengthMismatch { .. }
+            | StoiError::InvalidRegion
+            | StoiError::Clipped { .. } => Stage::Input,
+            #[cfg(feature = "wav")]
+            StoiError::Wav(_) => Stage::Input,
+            #[cfg(feature = "debug-npy")]
+            StoiError::Npy(_) => Stage::Input,
+            StoiError::InvalidSampleRate(_) => Stage::Resample,
+            StoiError::NotEnoughFrames { .. }
+            | StoiError::SilentSignal
+            | StoiError::SignalTooShort { .. } => Stage::Framing,
+            StoiError::SpectrumTooShort { .. } => Stage::Octave,
+        }
+    }
+}
+
+const NOT_ENOUGH_FRAMES: &str = "Not enough STFT frames to compute intermediate \
 intelligibility measure after removing silent \
 frames. Please check you wav files";
 
 /// Implement Display for human-readable messages
-impl Display for NotEnoughFramesError {
+impl Display for StoiError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", MESSAGE)
+        match self {
+            StoiError::NotEnoughFrames {
+                valid_frames,
+                segment_frames,
+            } => write!(
+                f,
+                "{} ({} frames remain, need at least {})",
+                NOT_ENOUGH_FRAMES, valid_frames, segment_frames
+            ),
+            StoiError::InvalidConfig(reason) => write!(f, "Invalid STOI config: {}", reason),
+            StoiError::SilentSignal => write!(f, "Clean signal is silent"),
+            StoiError::SignalTooShort { len, fs, min_len } => write!(
+                f,
+                "Signals are too short to compute a single STOI segment: \
+                {} samples at {}Hz, need at least {}",
+                len, fs, min_len
+            ),
+            StoiError::SignalTooLong { len, max_len } => write!(
+                f,
+                "Signals are too long: {} samples at 10kHz, at most {} are allowed. \
+                Score regions of the signals with stoi_region, or raise max_signal_len",
+                len, max_len
+            ),
+            StoiError::LengthMismatch { x_len, y_len } => write!(
+                f,
+                "Signals have different lengths: {} and {} samples",
+                x_len, y_len
+            ),
+            StoiError::InvalidRegion => write!(f, "Region is out of the signals bounds"),
+            StoiError::Clipped { count } => {
+                write!(f, "Signal is clipped: {} samples are at full scale", count)
+            }
+            StoiError::SpectrumTooShort { bins, min_bins } => write!(
+                f,
+                "Spectrogram is too short: {} frequency bins, need at least {}",
+                bins, min_bins
+            ),
+            #[cfg(feature = "wav")]
+            StoiError::Wav(reason) => write!(f, "Invalid WAV data: {}", reason),
+            #[cfg(feature = "debug-npy")]
+            StoiError::Npy(reason) => write!(f, "Could not write NPY array: {}", reason),
+            StoiError::InvalidSampleRate(fs) => {
+                write!(f, "Unsupported sampling frequency: {}Hz", fs)
+            }
+        }
     }
 }
 
 /// Implement std::error::Error so it can be used with `?`
-impl std::error::Error for NotEnoughFramesError {}
+impl std::error::Error for StoiError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_contain_the_relevant_numbers() {
+        let messages = [
+            (
+                StoiError::NotEnoughFrames {
+                    valid_frames: 12,
+                    segment_frames: 30,
+                },
+                vec!["12 frames remain", "at least 30"],
+            ),
+            (
+                StoiError::SignalTooShort {
+                    len: 1_000,
+                    fs: 16_000,
+                    min_len: 6_556,
+                },
+                vec!["1000 samples at 16000Hz", "at least 6556"],
+            ),
+            (
+                StoiError::SignalTooLong {
+                    len: 20_000,
+                    max_len: 10_000,
+                },
+                vec!["20000 samples", "at most 10000"],
+            ),
+            (
+                StoiError::LengthMismatch {
+                    x_len: 100,
+                    y_len: 99,
+                },
+                vec!["100 and 99 samples"],
+            ),
+            (StoiError::Clipped { count: 42 }, vec!["42 samples"]),
+            (
+                StoiError::SpectrumTooShort {
+                    bins: 129,
+                    min_bins: 257,
+                },
+                vec!["129 frequency bins", "at least 257"],
+            ),
+            (StoiError::InvalidSampleRate(12_345), vec!["12345Hz"]),
+            (
+                StoiError::InvalidConfig("hop_length must be positive"),
+                vec!["hop_length must be positive"],
+            ),
+        ];
+
+        for (error, parts) in messages {
+            let message = error.to_string();
+            for part in parts {
+                assert!(message.contains(part), "{message:?} misses {part:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn errors_report_the_stage_they_occur_in() {
+        let x = crate::test_signals::noise(16_000, 1);
+        let stage = |result: Result<f32>| result.unwrap_err().context();
+
+        assert_eq!(stage(crate::stoi(&x, &x[1..], 16_000, false)), Stage::Input);
+        assert_eq!(stage(crate::stoi(&x, &x, 0, false)), Stage::Resample);
+        assert_eq!(
+            stage(crate::stoi(vec![0.0; 16_000], &x, 16_000, false)),
+            Stage::Framing
+        );
+        assert_eq!(
+            stage(crate::stoi(&x[..4_000], &x[..4_000], 16_000, false)),
+            Stage::Framing
+        );
+        let spectrogram = faer::Mat::<f32>::zeros(100, 4);
+        assert_eq!(
+            stage(crate::octave_bands(spectrogram.as_ref()).map(|_| 0.0)),
+            Stage::Octave
+        );
+    }
+}
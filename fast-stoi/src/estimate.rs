@@ -0,0 +1,190 @@
+//! Resource estimates computed from signal lengths
+
+use std::mem::size_of;
+
+use crate::{
+    config::StoiConfig,
+    constants::{FFT_BINS, FRAME_LENGTH, FS},
+    frames, resample,
+};
+
+/// Estimate the amount of bytes allocated by a STOI computation on 2 signals
+/// of length `signal_len` sampled at `fs_sig`.
+///
+/// Counted allocations, for both signals:
+/// * resampled signals and the padded copies used by the resampler
+/// * frame energies, mask and valid frame indices
+/// * spectrograms, frequency band spectrograms and segments
+/// * segment correlations
+///
+/// The estimate assumes that no frame is silent, which is the worst case.
+/// Cached resampling filters and FFT plans are shared across calls and are excluded.
+/// Estimates saturate to `usize::MAX` for huge signals. They are 0 for a zero
+/// sampling frequency or an invalid configuration, which scoring functions
+/// reject before any allocation.
+pub fn estimate_memory(signal_len: usize, fs_sig: usize, config: &StoiConfig) -> usize {
+    if fs_sig == 0 || config.validate().is_err() {
+        return 0;
+    }
+
+    let float = size_of::<f32>();
+    let mut floats: usize = 0;
+
+    // Resampled signals along with their padded input copies
+    let len = if fs_sig != FS {
+        let resampled_len = resample::resampled_len(signal_len, fs_sig, FS);
+        floats = floats.saturating_add(signal_len.saturating_add(resampled_len).saturating_mul(2));
+        resampled_len
+    } else {
+        signal_len
+    };
+
+    // Frame energies, mask and valid frame indices (frames themselves are not stored)
+    let n_frames = frames::frame_count(len, config.hop_length);
+    floats = floats.saturating_add(n_frames);
+    let mask = n_frames.saturating_mul(size_of::<bool>() + size_of::<usize>());
+
+    // Spectrograms and frequency bands (the last valid frame is discarded)
+    let num_bands = config.band_scheme.num_bands();
+    let count = n_frames.saturating_sub(1);
+    floats = floats.saturating_add(
+        FFT_BINS
+            .saturating_add(num_bands)
+            .saturating_mul(2)
+            .saturating_mul(count),
+    );
+
+    // Segments and their correlations
+    let n_segments = count.saturating_sub(config.segment_frames) + 1;
+    floats = floats.saturating_add(
+        config
+            .segment_frames
+            .saturating_mul(2)
+            .saturating_add(1)
+            .saturating_mul(n_segments)
+            .saturating_mul(num_bands),
+    );
+
+    floats.saturating_mul(float).saturating_add(mask)
+}
+
+/// Minimum length of signals sampled at `fs_sig` to yield at least one segment,
+/// assuming that no frame is silent.
+///
+/// Lengths that do not fit in `usize` saturate to `usize::MAX`.
+pub fn min_signal_len(fs_sig: usize, config: &StoiConfig) -> usize {
+    // One frame more than the segment length, as the last valid frame is discarded
+    let len = (FRAME_LENGTH as u128 + 1)
+        .saturating_add(config.min_valid_frames() as u128 * config.hop_length as u128);
+
+    // Smallest length that resamples to at least `len` samples
+    let len = len.saturating_mul(fs_sig as u128).div_ceil(FS as u128);
+    usize::try_from(len).unwrap_or(usize::MAX)
+}
+
+/// Amount of segments yielded by signals of length `signal_len` sampled at `fs_sig`,
+/// to skip signals that are too short without running the computation.
+///
+/// This is an upper bound, as it assumes that no frame is silent:
+/// removing silent frames only ever decreases the amount of segments.
+/// Zero sampling frequencies and invalid configurations yield no segments.
+pub fn estimate_segments(signal_len: usize, fs_sig: usize, config: &StoiConfig) -> usize {
+    if fs_sig == 0 || config.validate().is_err() {
+        return 0;
+    }
+
+    let len = resample::resampled_len(signal_len, fs_sig, FS);
+
+    // The last valid frame is discarded
+    let count = frames::frame_count(len, config.hop_length).saturating_sub(1);
+
+    if count >= config.min_valid_frames() {
+        count - config.min_valid_frames() + 1
+    } else if config.pad_short && count > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_estimates_increase_with_length() {
+        let config = StoiConfig::default();
+        for fs in [FS, 16_000, 48_000] {
+            let estimates: Vec<usize> = [0, 1_000, 10_000, 100_000, 1_000_000]
+                .iter()
+                .map(|&len| estimate_memory(len, fs, &config))
+                .collect();
+            assert!(estimates.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+
+        // Resampled signals need their copies at 10kHz as well
+        assert!(estimate_memory(160_000, 16_000, &config) > estimate_memory(100_000, FS, &config));
+    }
+
+    #[test]
+    fn memory_estimates_saturate_without_panicking() {
+        let config = StoiConfig::default();
+        assert_eq!(estimate_memory(usize::MAX, FS, &config), usize::MAX);
+        assert_eq!(estimate_memory(usize::MAX, 1, &config), usize::MAX);
+        assert_eq!(estimate_memory(1_000, 0, &config), 0);
+
+        let invalid = StoiConfig {
+            hop_length: 0,
+            ..StoiConfig::default()
+        };
+        assert_eq!(estimate_memory(1_000, FS, &invalid), 0);
+    }
+
+    #[test]
+    fn segment_estimates_match_the_correlation_matrix() {
+        let x = crate::test_signals::noise(48_000, 1);
+        let y = crate::test_signals::noisy(&x, 1.0, 2);
+        let config = StoiConfig::default();
+
+        for fs in [FS, 16_000] {
+            let matrix = crate::stoi_correlation_matrix(&x, &y, fs, &config).unwrap();
+            assert_eq!(estimate_segments(x.len(), fs, &config), matrix.nrows());
+        }
+
+        let trimmed = StoiConfig {
+            trim_segments: 4,
+            ..StoiConfig::default()
+        };
+        let matrix = crate::stoi_correlation_matrix(&x, &y, 16_000, &trimmed).unwrap();
+        assert_eq!(estimate_segments(x.len(), 16_000, &trimmed), matrix.nrows());
+    }
+
+    #[test]
+    fn segment_estimates_do_not_panic() {
+        let config = StoiConfig::default();
+        assert_eq!(estimate_segments(1_000, FS, &config), 0);
+        assert_eq!(estimate_segments(1_000, 0, &config), 0);
+        assert!(estimate_segments(usize::MAX, 1, &config) > 0);
+
+        let invalid = StoiConfig {
+            hop_length: 0,
+            ..StoiConfig::default()
+        };
+        assert_eq!(estimate_segments(100_000, FS, &invalid), 0);
+    }
+
+    #[test]
+    fn min_signal_lengths_saturate() {
+        let config = StoiConfig::default();
+        assert_eq!(min_signal_len(FS, &config), FRAME_LENGTH + 1 + 30 * 128);
+        // Products of huge frequencies overflow usize before the division
+        let expected = (4097 * usize::MAX as u128).div_ceil(FS as u128);
+        assert_eq!(min_signal_len(usize::MAX, &config) as u128, expected);
+
+        let config = StoiConfig {
+            trim_segments: usize::MAX / 2,
+            ..StoiConfig::default()
+        };
+        assert_eq!(min_signal_len(FS, &config), usize::MAX);
+    }
+}
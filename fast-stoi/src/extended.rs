@@ -1,36 +1,54 @@
 //! Extended STOI computation from octave segment spectrograms
-
-use std::f32::EPSILON;
+//!
+//! As in the reference implementation, silent frames are removed before
+//! anything else using the clean signal energies only, and the remaining
+//! frames are overlap-added back into a signal before the STFT.
+//! The segments are then normalized on the resulting octave bands:
+//! first along time for each band, then along bands for each time frame.
+//!
+//! The normalization matches `row_col_normalize` of pystoi 0.4.1, which adds
+//! noise of the float64 epsilon to the segments instead of regularizing norms.
+//! Norms are regularized by this epsilon rather than `f32::EPSILON`, which would
+//! otherwise shift the scores of quiet signals, whose band energies are small.
 
 use faer::prelude::*;
 
-use crate::constants::{NUM_BANDS, SEGMENT_LENGTH};
+use crate::constants::REFERENCE_EPS;
 
-/// Compute the extended STOI from octave segment spectrograms of the clean and processed signals.
+/// Compute the extended STOI correlations from octave segment spectrograms of the clean and processed signals.
 /// The segments have shapes (segment_length, num_segments * num_bands).
-pub fn from_segments(mut x_segments: MatMut<f32>, mut y_segments: MatMut<f32>) -> f32 {
-    row_col_normalize(x_segments.as_mut());
-    row_col_normalize(y_segments.as_mut());
+/// Returns the contribution of every segment band to the score, with shape (num_bands, num_segments).
+pub fn from_segments(
+    mut x_segments: MatMut<f32>,
+    mut y_segments: MatMut<f32>,
+    num_bands: usize,
+) -> Mat<f32> {
+    row_col_normalize(x_segments.as_mut(), num_bands);
+    row_col_normalize(y_segments.as_mut(), num_bands);
 
-    let n_segments = x_segments.ncols();
+    // Each segment correlation is spread over its bands
+    let scale = num_bands as f32 / x_segments.nrows() as f32;
+    let mut correlations = Mat::<f32>::zeros(num_bands, x_segments.ncols() / num_bands);
 
-    let dotted: f32 = x_segments
+    x_segments
         .col_iter()
         .zip(y_segments.col_iter())
-        .map(|(x_col, y_col)| x_col.transpose() * y_col)
-        .sum();
+        .zip(correlations.col_iter_mut().flat_map(|col| col.iter_mut()))
+        .for_each(|((x_col, y_col), correlation)| {
+            *correlation = (x_col.transpose() * y_col) * scale;
+        });
 
-    dotted / (n_segments as f32) * (NUM_BANDS as f32 / SEGMENT_LENGTH as f32)
+    correlations
 }
 
 /// Normalize segments both along columns, and along rows by band subgroups.
-fn row_col_normalize(mut mat: MatMut<f32>) {
+fn row_col_normalize(mut mat: MatMut<f32>, num_bands: usize) {
     normalize_cols(mat.as_mut());
 
     // Group segments by bands
-    let band_segments = mat.ncols() / NUM_BANDS;
+    let band_segments = mat.ncols() / num_bands;
     for i in 0..band_segments {
-        let mut submat = mat.as_mut().subcols_mut(i * NUM_BANDS, NUM_BANDS);
+        let mut submat = mat.as_mut().subcols_mut(i * num_bands, num_bands);
         normalize_rows(submat.as_mut());
     }
 }
@@ -45,7 +63,7 @@ fn normalize_cols(mat: MatMut<f32>) {
         });
 
         // NOTE: faer's .norm_l2 is very slow for such small vectors
-        let norm2 = (col.as_ref().iter().map(|x| x * x).sum::<f32>()).sqrt() + EPSILON;
+        let norm2 = (col.as_ref().iter().map(|x| x * x).sum::<f32>()).sqrt() + REFERENCE_EPS;
         col.iter_mut().for_each(|x| {
             *x /= norm2;
         });
@@ -61,7 +79,7 @@ fn normalize_rows(mat: MatMut<f32>) {
             *x -= mean;
         });
         // NOTE: faer's .norm_l2 is very slow for such small vectors
-        let norm2 = (row.as_ref().iter().map(|x| x * x).sum::<f32>()).sqrt() + EPSILON;
+        let norm2 = (row.as_ref().iter().map(|x| x * x).sum::<f32>()).sqrt() + REFERENCE_EPS;
         row.iter_mut().for_each(|x| {
             *x /= norm2;
         });
@@ -1,173 +1,1018 @@
 //! Slice, filter and preprocess audio frames.
 
+use std::borrow::Cow;
+
 use faer::prelude::*;
 use lazy_static::lazy_static;
 use windowfunctions::{Symmetry, WindowFunction, window};
 
-use crate::constants::{DYNAMIC_RANGE, FRAME_LENGTH, HALF_FRAME, HOP_LENGTH, SEGMENT_LENGTH};
+use crate::{
+    config::{FrameWindow, PartialPolicy, StoiConfig},
+    constants::{DYNAMIC_RANGE, FRAME_LENGTH, HALF_FRAME, HOP_LENGTH, NORMALIZED_RMS},
+    errors::{Result, StoiError},
+};
+
+/// Window applied to frames, along with the windows effectively applied
+/// by overlap-adding adjacent frames and slicing them again.
+pub struct FrameWindows {
+    /// Frame window (trimmed hann window by default)
+    pub window: Col<f32>,
+    /// Frame window with half overlap with another frame window at the end
+    pub window_start: Col<f32>,
+    /// Frame window with overlapping frame windows added at both ends
+    pub window_center: Col<f32>,
+    // NOTE: we don't need the end window, that frame is discarded
+}
+
+// The combined windows below mimic overlap-adding frames with half overlap,
+// which requires an even frame length.
+const _: () = assert!(FRAME_LENGTH.is_multiple_of(2), "FRAME_LENGTH must be even");
+
+/// Symmetric hann window of length `frame_length` without its zero endpoints,
+/// as `np.hanning(frame_length + 2)[1:-1]`.
+///
+/// Both parities are supported: the full symmetric window of length
+/// `frame_length + 2` is trimmed by one sample at each end, which preserves
+/// its symmetry. Odd lengths have a single peak sample of value 1, while even
+/// lengths have two equal center samples.
+pub fn hann(frame_length: usize) -> Col<f32> {
+    window(frame_length + 2, WindowFunction::Hann, Symmetry::Symmetric)
+        .skip(1)
+        .take(frame_length)
+        .collect::<Col<f32>>()
+}
 
-struct FrameWindows {
-    /// Trimmed hann window
-    pub hann: Col<f32>,
-    /// Hann window with half overlap with another hann window at the end
-    pub hann_start: Col<f32>,
-    /// Hann window with overlapping hann windows added at both ends
-    pub hann_center: Col<f32>,
-    // NOTE: we don't need hann end, that frame is discarded
+/// Symmetric bartlett (triangular) window of length `frame_length`
+/// without its zero endpoints, as `np.bartlett(frame_length + 2)[1:-1]`.
+pub fn bartlett(frame_length: usize) -> Col<f32> {
+    window(
+        frame_length + 2,
+        WindowFunction::Bartlett,
+        Symmetry::Symmetric,
+    )
+    .skip(1)
+    .take(frame_length)
+    .collect::<Col<f32>>()
+}
+
+/// Symmetric welch (parabolic) window of length `frame_length`
+/// without its zero endpoints, `1 - ((n - c) / c)²` with `c = (frame_length + 1) / 2`
+/// and `n` in `1..=frame_length`.
+pub fn welch(frame_length: usize) -> Col<f32> {
+    let center = (frame_length + 1) as f32 / 2.0;
+    Col::from_fn(frame_length, |n| {
+        let x = (n + 1) as f32 / center - 1.0;
+        1.0 - x * x
+    })
+}
+
+impl FrameWindow {
+    /// Samples of the window, of the frame length (256)
+    pub fn coefficients(&self) -> Vec<f32> {
+        frame_windows(*self).window.iter().copied().collect()
+    }
 }
 
 impl FrameWindows {
-    fn new() -> Self {
-        let hann = window(FRAME_LENGTH + 2, WindowFunction::Hann, Symmetry::Symmetric)
-            .skip(1)
-            .take(FRAME_LENGTH)
-            .collect::<Col<f32>>();
-
-        // 1. Combine hann windows to mimic slicing + overlap-adding
-        let mut hann_start = hann.clone();
-        let mut slice = hann_start.subrows_mut(HALF_FRAME, HALF_FRAME);
-        slice += &hann.subrows(0, HALF_FRAME);
-        // 2. Apply hann again to account for the reslicing just before rfft
-        zip!(&mut hann_start, &hann).for_each(|unzip!(w1, &w2)| *w1 *= w2);
-
-        // 1. Combine hann windows to mimic slicing + overlap-adding
-        let mut hann_center = hann.clone();
-        let mut slice = hann_center.subrows_mut(0, HALF_FRAME);
-        slice += &hann.subrows(HALF_FRAME, HALF_FRAME);
-        let mut slice = hann_center.subrows_mut(HALF_FRAME, HALF_FRAME);
-        slice += &hann.subrows(0, HALF_FRAME);
-        // 2. Apply hann again to account for the reslicing just before rfft
-        zip!(&mut hann_center, &hann).for_each(|unzip!(w1, &w2)| *w1 *= w2);
+    /// Combine a frame window of length `FRAME_LENGTH`
+    pub fn new(window: Col<f32>) -> Self {
+        // 1. Combine frame windows to mimic slicing + overlap-adding
+        let mut window_start = window.clone();
+        let mut slice = window_start.subrows_mut(HALF_FRAME, HALF_FRAME);
+        slice += &window.subrows(0, HALF_FRAME);
+        // 2. Apply the window again to account for the reslicing just before rfft
+        zip!(&mut window_start, &window).for_each(|unzip!(w1, &w2)| *w1 *= w2);
+
+        // 1. Combine frame windows to mimic slicing + overlap-adding
+        let mut window_center = window.clone();
+        let mut slice = window_center.subrows_mut(0, HALF_FRAME);
+        slice += &window.subrows(HALF_FRAME, HALF_FRAME);
+        let mut slice = window_center.subrows_mut(HALF_FRAME, HALF_FRAME);
+        slice += &window.subrows(0, HALF_FRAME);
+        // 2. Apply the window again to account for the reslicing just before rfft
+        zip!(&mut window_center, &window).for_each(|unzip!(w1, &w2)| *w1 *= w2);
 
         Self {
-            hann,
-            hann_start,
-            hann_center,
+            window,
+            window_start,
+            window_center,
         }
     }
 }
 
 lazy_static! {
-    static ref FRAME_WINDOWS: FrameWindows = FrameWindows::new();
+    /// Windows of the reference implementation
+    pub static ref FRAME_WINDOWS: FrameWindows = FrameWindows::new(hann(FRAME_LENGTH));
+    static ref BARTLETT_WINDOWS: FrameWindows = FrameWindows::new(bartlett(FRAME_LENGTH));
+    static ref WELCH_WINDOWS: FrameWindows = FrameWindows::new(welch(FRAME_LENGTH));
+}
+
+/// Windows of a frame window
+pub fn frame_windows(window: FrameWindow) -> &'static FrameWindows {
+    match window {
+        FrameWindow::Hann => &FRAME_WINDOWS,
+        FrameWindow::Bartlett => &BARTLETT_WINDOWS,
+        FrameWindow::Welch => &WELCH_WINDOWS,
+    }
+}
+
+/// Trim the leading and trailing silence of 2 signals by the same sample range.
+///
+/// As for silent frames, silence is detected on the clean signal `x` only:
+/// samples more than `DYNAMIC_RANGE` (40dB) below its peak absolute value are silent.
+pub fn trim_silence<'a>(x: &'a [f32], y: &'a [f32]) -> (&'a [f32], &'a [f32]) {
+    let peak = x
+        .iter()
+        .fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+    let threshold = peak * 10.0_f32.powf(-DYNAMIC_RANGE / 20.0);
+
+    let start = x.iter().position(|sample| sample.abs() >= threshold);
+    let end = x.iter().rposition(|sample| sample.abs() >= threshold);
+
+    match (start, end) {
+        (Some(start), Some(end)) => (&x[start..=end], &y[start..=end]),
+        _ => (x, y),
+    }
+}
+
+/// Scale a signal to an RMS of `NORMALIZED_RMS`.
+///
+/// Signals with no energy or non-finite samples are borrowed unchanged.
+pub fn normalize_rms(x: &[f32]) -> Cow<'_, [f32]> {
+    let energy: f32 = x.iter().map(|sample| sample * sample).sum();
+    let rms = (energy / x.len() as f32).sqrt();
+    if !rms.is_finite() || rms == 0.0 {
+        return Cow::Borrowed(x);
+    }
+
+    let gain = NORMALIZED_RMS / rms;
+    Cow::Owned(x.iter().map(|sample| sample * gain).collect())
+}
+
+/// Amount of frames sliced every `hop_length` samples from a signal
+/// of length `len` by `process_frames`.
+pub fn frame_count(len: usize, hop_length: usize) -> usize {
+    if len > FRAME_LENGTH {
+        1 + (len - FRAME_LENGTH - 1) / hop_length
+    } else {
+        0
+    }
+}
+
+/// Pad a signal so that its trailing samples in no complete frame form a last frame.
+///
+/// Signals whose samples are all in a frame, or too short for any frame,
+/// are borrowed. Padded signals are one sample longer than their last frame,
+/// as frames must stop strictly before the end of the signal.
+pub fn pad_partial(x: &[f32], hop_length: usize, policy: PartialPolicy) -> Cow<'_, [f32]> {
+    let n = frame_count(x.len(), hop_length);
+    if n == 0 || (n - 1) * hop_length + FRAME_LENGTH >= x.len() {
+        return Cow::Borrowed(x);
+    }
+
+    // Add a frame starting at the first hop covering the last sample.
+    // Less than a frame is padded, which is shorter than the signal.
+    let last_start = (x.len() - FRAME_LENGTH).div_ceil(hop_length) * hop_length;
+    let padding = last_start + FRAME_LENGTH + 1 - x.len();
+    let reflected = (0..padding).map(|i| x[x.len() - 2 - i]);
+
+    let padded = match policy {
+        PartialPolicy::Drop => return Cow::Borrowed(x),
+        PartialPolicy::ZeroPad => x
+            .iter()
+            .copied()
+            .chain(std::iter::repeat_n(0.0, padding))
+            .collect(),
+        PartialPolicy::Reflect => x.iter().copied().chain(reflected).collect(),
+    };
+
+    Cow::Owned(padded)
+}
+
+/// Lazily slice a signal into overlapping frames windowed by a trimmed hann window,
+/// without materializing all frames at once.
+///
+/// Frames start every `hop_length` samples as in the STOI computation, which yields
+/// `1 + (len - frame_length - 1) / hop_length` frames. The iterator borrows `x`
+/// until it is dropped, while every yielded frame is an owned copy, so that frames
+/// can be kept or folded over without holding the signal.
+/// The STOI computation itself needs the energies of all frames before
+/// removing silent frames, and cannot process frames in a streaming fashion.
+/// A zero `frame_length` or `hop_length` yields no frames.
+pub fn frames_iter(
+    x: &[f32],
+    frame_length: usize,
+    hop_length: usize,
+) -> impl Iterator<Item = Vec<f32>> + '_ {
+    let window = hann(frame_length);
+    let end = match (frame_length, hop_length) {
+        (0, _) | (_, 0) => 0,
+        _ => x.len().saturating_sub(frame_length),
+    };
+
+    (0..end).step_by(hop_length.max(1)).map(move |start| {
+        x[start..start + frame_length]
+            .iter()
+            .zip(window.iter())
+            .map(|(sample, w)| sample * w)
+            .collect()
+    })
+}
+
+/// Random access source of signal samples, for signals that are not
+/// contiguous in memory (ring buffers, chunked or memory-mapped storage).
+///
+/// Frames are read in increasing order of their start, each at most once,
+/// but neighbouring parts of the signal may be read again when overlap-adding
+/// frames around silent gaps. Reads are always within `0..len()`.
+pub trait FrameSource {
+    /// Total amount of samples
+    fn len(&self) -> usize;
+
+    /// Whether the source has no samples
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Copy the `out.len()` samples starting at `start` into `out`
+    fn read(&self, start: usize, out: &mut [f32]);
+}
+
+impl FrameSource for [f32] {
+    fn len(&self) -> usize {
+        <[f32]>::len(self)
+    }
+
+    fn read(&self, start: usize, out: &mut [f32]) {
+        out.copy_from_slice(&self[start..start + out.len()]);
+    }
 }
 
-/// Slice 2 input signals into overlapping frames and
-/// applies a hann window to each frame.
-/// The frames are then filtered based on their energy.
+/// Valid frames of 2 signals after removing silent frames.
 ///
-/// Returns 2D arrays containing the frames with shape
-/// (frame_length, n_frames) along with a boolean mask
-/// and the total amount of valid frames.
+/// Frames are not stored: each valid frame is read again from its signal
+/// and windowed when needed, which avoids allocating a matrix of all frames
+/// (twice as large as the signal with half overlap).
+pub struct ValidFrames<'a, S: FrameSource + ?Sized> {
+    x: &'a S,
+    y: &'a S,
+    windows: &'a FrameWindows,
+    hop_length: usize,
+    /// Indices of the valid frames among all frames,
+    /// including the discarded last valid frame
+    valid: Vec<usize>,
+    /// Amount of valid frames
+    pub count: usize,
+    /// Amount of frames before removing silent frames
+    pub total: usize,
+}
+
+impl<S: FrameSource + ?Sized> ValidFrames<'_, S> {
+    /// Write the `k`-th valid frame of the clean signal into `out` (of length `FRAME_LENGTH`)
+    pub fn read_x(&self, k: usize, out: &mut [f32]) {
+        self.read(self.x, k, out);
+    }
+
+    /// Write the `k`-th valid frame of the processed signal into `out` (of length `FRAME_LENGTH`)
+    pub fn read_y(&self, k: usize, out: &mut [f32]) {
+        self.read(self.y, k, out);
+    }
+
+    /// Mask of the valid frames among all frames, without the discarded last valid frame
+    pub fn mask(&self) -> Vec<bool> {
+        let mut mask = vec![false; self.total];
+        self.valid[..self.count]
+            .iter()
+            .for_each(|&i| mask[i] = true);
+        mask
+    }
+
+    fn read(&self, signal: &S, k: usize, out: &mut [f32]) {
+        debug_assert!(k < self.count, "Frame {} is not valid", k);
+        signal.read(self.valid[k] * self.hop_length, out);
+        overlap_add(
+            ColMut::from_slice_mut(out),
+            signal,
+            &self.valid,
+            k,
+            self.hop_length,
+            self.windows,
+        );
+    }
+}
+
+/// Slice 2 input signals into overlapping frames,
+/// and filter them based on the energy of the clean frames.
+///
+/// Returns the valid frames, which are read on demand with the window
+/// resulting from overlap-adding the valid frames and slicing them again.
+/// Returns [`StoiError::SilentSignal`] if the clean signal has no energy.
+/// Frames are windowed with `windows`, which default to those of `config.frame_window`.
+/// Signals of the same length, longer than a frame, and a valid `config` are
+/// not checked, see [`crate::resample_and_frame`] for a checked version.
+///
+/// Frames start every `config.hop_length` samples, which yields
+/// `1 + (len - frame_length - 1) / hop_length` frames. Smaller hops
+/// increase the amount of frames and the cost of every later stage accordingly.
 ///
 /// Performance notes:
 /// Energy-based filtering is performed once all energies have been computed.
-/// For this reason, we cannot know beforehand which frames are to be discarded,
-/// hence why we store all frames in an intermediate 2D array.
-/// In order to avoid reallocations, we return the unfiltered 2D array along
-/// with a boolean mask indicating which frames to keep.
-pub fn process_frames(x: &[f32], y: &[f32]) -> (Mat<f32>, Mat<f32>, Col<bool>, usize) {
-    // 1. Compute frames and energies
-    let n = 1 + (x.len() - FRAME_LENGTH - 1) / HOP_LENGTH;
-    let mut x_frames = Mat::<f32>::zeros(FRAME_LENGTH, n);
-    let mut y_frames = Mat::<f32>::zeros(FRAME_LENGTH, n);
+/// For this reason, we cannot know beforehand which frames are to be discarded.
+/// Rather than storing all frames in an intermediate 2D array, only their
+/// energies are stored, and the valid frames are read from the signals again.
+/// Frames are copied once more into the FFT input buffer, which is intrinsic:
+/// they overlap in the signals and are windowed before the FFT.
+pub fn process_frames<'a, S: FrameSource + ?Sized>(
+    x: &'a S,
+    y: &'a S,
+    config: &StoiConfig,
+    windows: &'a FrameWindows,
+) -> Result<ValidFrames<'a, S>> {
+    // 1. Compute frame energies
+    let hop_length = config.hop_length;
+    let n = frame_count(x.len(), hop_length);
     let mut energies = Col::<f32>::zeros(n);
+    let mut frame = [0.0; FRAME_LENGTH];
+
+    for (i, start) in (0..x.len() - FRAME_LENGTH).step_by(hop_length).enumerate() {
+        x.read(start, &mut frame);
+        energies[i] = frame_energy(ColRef::from_slice(&frame), windows.window.as_ref());
+    }
+
+    // 2. Compute frame mask based on energies
+    if is_silent(energies.as_ref()) {
+        return Err(StoiError::SilentSignal);
+    }
+    let (mask, count) = compute_frame_mask(
+        energies.as_ref(),
+        config.vad_hysteresis,
+        vad_reference(config, windows.window.as_ref()),
+    );
+    // An absolute reference may discard every frame
+    if count == 0 {
+        return Err(StoiError::NotEnoughFrames {
+            valid_frames: 0,
+            segment_frames: config.min_valid_frames(),
+        });
+    }
+
+    // 3. Discard the last valid frame as the original implementation does (bad slicing).
+    // It is still needed to overlap-add the frames before it.
+    let valid = (0..n).filter(|&i| mask[i]).collect::<Vec<_>>();
+
+    Ok(ValidFrames {
+        x,
+        y,
+        windows,
+        hop_length,
+        valid,
+        count: count - 1, // account for the discarded last frame
+        total: n,
+    })
+}
 
-    for (i, start) in (0..x.len() - FRAME_LENGTH).step_by(HOP_LENGTH).enumerate() {
-        // Compute the energy for the current x frame
-        let end = start + FRAME_LENGTH;
+/// Absolute reference energy in dB of silent frame removal, if any,
+/// in the same windowed energy scale as `frame_energy`.
+///
+/// Normalized references are raw frame energies: they are converted
+/// by adding the window energy `10 * log10(sum(w²))`.
+fn vad_reference(config: &StoiConfig, window: ColRef<f32>) -> Option<f32> {
+    config.vad_reference_db.map(|reference| {
+        if config.vad_normalize_window {
+            let window_energy: f32 = window.iter().map(|w| w * w).sum();
+            reference + 10.0 * window_energy.log10()
+        } else {
+            reference
+        }
+    })
+}
 
-        let mut x_frame = x_frames.col_mut(i);
-        let mut y_frame = y_frames.col_mut(i);
+/// Compute the energy in dB of a frame after applying the frame window.
+fn frame_energy(frame: ColRef<f32>, window: ColRef<f32>) -> f32 {
+    // Note that we do not apply the window to the frame in place,
+    // because due to the original stoi implementation
+    // 1. applying the window
+    // 2. rebuilding the signal by overlap-adding the frames
+    // 3. slicing and applying the window again
+    // the resulting window that is effectively applied to each frame
+    // is a little different.
+    let frame_norm = zip!(frame, window)
+        .map(|unzip!(x, w)| (x * w).powi(2))
+        .sum()
+        .sqrt();
 
-        // Copy frames
-        x_frame.copy_from(ColRef::from_slice(&x[start..end]));
-        y_frame.copy_from(ColRef::from_slice(&y[start..end]));
+    20.0 * (frame_norm + f32::EPSILON).log10()
+}
 
-        // Compute the frame norm after applying hann window
-        // Note that we do not apply hann window to the frame in place,
-        // because due to the original stoi implementation
-        // 1. applying hann
-        // 2. rebuilding the signal by overlap-adding the frames
-        // 3. slicing and applying hann again
-        // the resulting window that is effectively applied to each frame
-        // is a little different.
-        let frame_norm = zip!(&x_frame, &FRAME_WINDOWS.hann)
-            .map(|unzip!(x, w)| (x * w).powi(2))
-            .sum()
-            .sqrt();
+/// Trace silent frame removal on a clean signal.
+///
+/// Returns for every frame whether it is kept as speech, along with its energy
+/// relative to the speech threshold in dB (negative below the threshold).
+/// Note that the full computation additionally discards the last kept frame.
+pub fn vad_trace(x: &[f32], config: &StoiConfig) -> Vec<(bool, f32)> {
+    let hop_length = config.hop_length;
+    let x = if config.normalize_rms {
+        normalize_rms(x)
+    } else {
+        Cow::Borrowed(x)
+    };
+    let x = pad_partial(&x, hop_length, config.include_partial);
+    let n = frame_count(x.len(), hop_length);
+    if n == 0 {
+        return Vec::new();
+    }
+
+    let window = frame_windows(config.frame_window).window.as_ref();
+    let energies = Col::<f32>::from_fn(n, |i| {
+        let start = i * hop_length;
+        frame_energy(ColRef::from_slice(&x[start..start + FRAME_LENGTH]), window)
+    });
+
+    let reference = vad_reference(config, window);
+    let (mask, _) = compute_frame_mask(energies.as_ref(), config.vad_hysteresis, reference);
+    let threshold = speech_threshold(energies.as_ref(), reference);
+
+    mask.iter()
+        .zip(energies.iter())
+        .map(|(&kept, &energy)| (kept, energy - threshold))
+        .collect()
+}
+
+/// Apply to the `k`-th valid frame of `signal` the window resulting from
+/// overlap-adding it with its neighbouring valid frames and slicing it again
+/// with the frame window. `valid` holds the indices of all valid frames,
+/// which start every `hop_length` samples.
+///
+/// With half overlap and neighbouring valid frames adjacent to the frame in the
+/// original signal, the overlapping samples are the frame's own and the
+/// precomputed combined windows are used. Otherwise, the overlapping parts of
+/// the neighbouring frames are added explicitly.
+fn overlap_add<S: FrameSource + ?Sized>(
+    mut frame: ColMut<f32>,
+    signal: &S,
+    valid: &[usize],
+    k: usize,
+    hop_length: usize,
+    windows: &FrameWindows,
+) {
+    let index = valid[k];
+    let prev = k.checked_sub(1).map(|k| valid[k]);
+    let next_adjacent = valid[k + 1] == index + 1;
 
-        // Compute frame energy
-        energies[i] = 20.0 * (frame_norm + f32::EPSILON).log10();
+    match prev {
+        None if hop_length == HOP_LENGTH && next_adjacent => {
+            zip!(&mut frame, &windows.window_start).for_each(|unzip!(w1, &w2)| *w1 *= w2);
+        }
+        Some(prev) if hop_length == HOP_LENGTH && next_adjacent && prev + 1 == index => {
+            zip!(&mut frame, &windows.window_center).for_each(|unzip!(w1, &w2)| *w1 *= w2);
+        }
+        _ => {
+            let window = windows.window.as_ref();
+            zip!(&mut frame, window).for_each(|unzip!(w1, &w2)| *w1 *= w2);
+            let mut buffer = [0.0; FRAME_LENGTH];
+
+            // Add the overlapping parts of the neighbouring valid frames
+            for offset in 1..FRAME_LENGTH.div_ceil(hop_length) {
+                let shift = offset * hop_length;
+                let overlap = FRAME_LENGTH - shift;
+
+                // End of a previous valid frame
+                if let Some(prev) = k.checked_sub(offset).map(|k| valid[k]) {
+                    let tail = &mut buffer[..overlap];
+                    signal.read(prev * hop_length + shift, tail);
+                    let tail = ColRef::from_slice(tail);
+                    zip!(
+                        frame.as_mut().subrows_mut(0, overlap),
+                        tail,
+                        window.subrows(shift, overlap)
+                    )
+                    .for_each(|unzip!(s, &x, &w)| *s += x * w);
+                }
+
+                // Start of a next valid frame
+                if let Some(&next) = valid.get(k + offset) {
+                    let head = &mut buffer[..overlap];
+                    signal.read(next * hop_length, head);
+                    let head = ColRef::from_slice(head);
+                    zip!(
+                        frame.as_mut().subrows_mut(shift, overlap),
+                        head,
+                        window.subrows(0, overlap)
+                    )
+                    .for_each(|unzip!(s, &x, &w)| *s += x * w);
+                }
+            }
+
+            // Slice again with the frame window
+            zip!(&mut frame, window).for_each(|unzip!(w1, &w2)| *w1 *= w2);
+        }
     }
+}
 
-    // 2. Compute frame mask based on energies
-    let threshold = energies.max().unwrap() - DYNAMIC_RANGE;
+/// Compute the mask of non-silent frames from their energies in dB.
+/// Returns the boolean mask along with the amount of valid frames.
+///
+/// Frames are valid if their energy is at most `DYNAMIC_RANGE` dB below
+/// the maximum frame energy, or below the absolute `reference` energy if any.
+/// With a `hysteresis` gap (in dB), speech starts when a frame reaches this
+/// threshold, and only stops when a frame falls more than `hysteresis` dB
+/// below it. This keeps short energy dips within speech.
+pub fn compute_frame_mask(
+    energies: ColRef<f32>,
+    hysteresis: Option<f32>,
+    reference: Option<f32>,
+) -> (Col<bool>, usize) {
+    let enter_threshold = speech_threshold(energies, reference);
+    let exit_threshold = enter_threshold - hysteresis.unwrap_or(0.0);
+
+    let mut speech = false;
     let mut count = 0;
-    let mut mask = energies
+    let mask = energies
         .iter()
         .map(|&e| {
-            let valid = e >= threshold;
-            count += valid as usize;
-            valid
+            speech = e >= enter_threshold || (speech && e >= exit_threshold);
+            count += speech as usize;
+            speech
         })
         .collect::<Col<_>>();
 
-    // 3. Discard the last valid frame as the original implementation does (bad slicing)
-    // and then apply the combined hann window to each valid frame to mimic the result
-    // from slicing, overlap-adding and slicing again.
-    let mut index = 0;
-    x_frames
-        .col_iter_mut()
-        .zip(y_frames.col_iter_mut())
-        .zip(mask.iter_mut())
-        .for_each(|((mut x_frame, mut y_frame), valid)| {
-            if !*valid {
-                return;
-            }
+    (mask, count)
+}
 
-            // First valid frame: apply hann_start
-            if index == 0 {
-                zip!(&mut x_frame, &FRAME_WINDOWS.hann_start).for_each(|unzip!(w1, &w2)| *w1 *= w2);
-                zip!(&mut y_frame, &FRAME_WINDOWS.hann_start).for_each(|unzip!(w1, &w2)| *w1 *= w2);
-            }
-            // Last valid frame: discard it
-            else if index == count - 1 {
-                *valid = false;
-            } else {
-                // Center frames: apply hann center
-                zip!(&mut x_frame, &FRAME_WINDOWS.hann_center)
-                    .for_each(|unzip!(w1, &w2)| *w1 *= w2);
-                zip!(&mut y_frame, &FRAME_WINDOWS.hann_center)
-                    .for_each(|unzip!(w1, &w2)| *w1 *= w2);
-            }
+/// Maximum frame energy in dB, skipping NaN energies.
+/// Returns negative infinity if all energies are NaN.
+fn max_energy(energies: ColRef<f32>) -> f32 {
+    energies
+        .iter()
+        .filter(|energy| !energy.is_nan())
+        .fold(f32::NEG_INFINITY, |max, &energy| max.max(energy))
+}
 
-            index += 1;
-        });
+/// Energy threshold in dB from which frames start being considered as speech,
+/// relative to the maximum frame energy or to an absolute `reference` energy
+fn speech_threshold(energies: ColRef<f32>, reference: Option<f32>) -> f32 {
+    reference.unwrap_or_else(|| max_energy(energies)) - DYNAMIC_RANGE
+}
 
-    count -= 1; // account for the discarded last frame
+/// Whether a signal has no energy: all its frames are zero or NaN.
+/// Every frame would otherwise be kept, as the threshold relative to the maximum
+/// energy would fall below the energy floor of zero frames.
+fn is_silent(energies: ColRef<f32>) -> bool {
+    max_energy(energies) <= 20.0 * f32::EPSILON.log10()
+}
 
-    (x_frames, y_frames, mask, count)
+/// Pad band spectrograms with shape (bands, frames) to `num_frames` frames
+/// by reflecting their frames back and forth, repeating the edge frames.
+pub fn reflect_pad(bands: MatRef<f32>, num_frames: usize) -> Mat<f32> {
+    let count = bands.ncols();
+    Mat::from_fn(bands.nrows(), num_frames, |band, frame| {
+        let frame = frame % (2 * count);
+        bands[(band, frame.min(2 * count - 1 - frame))]
+    })
 }
 
-/// Slice octave band spectrogram into overlapping segments
-/// Shapes: (frames, bands) -> (N, n_segments * bands)
+/// Slice octave band spectrogram into overlapping segments of `segment_length` frames
+/// Shapes: (frames, bands) -> (segment_length, n_segments * bands)
 ///
 /// We copy the segments into a new array because we need to perform per-segment
 /// mutating operations later.
 /// Because x and y will be compared on a per-segment basis, we merge the
 /// n_segments and bands dimensions for efficient storage and iteration.
-pub fn segments(x_bands: MatRef<f32>) -> Mat<f32> {
+pub fn segments(x_bands: MatRef<f32>, segment_length: usize) -> Mat<f32> {
     let n_bands = x_bands.ncols();
     let n_frames = x_bands.nrows();
-    let n_segments = n_frames.saturating_sub(SEGMENT_LENGTH) + 1;
+    let n_segments = n_frames.saturating_sub(segment_length) + 1;
 
-    let mut segments = Mat::<f32>::zeros(SEGMENT_LENGTH, n_segments * n_bands);
+    let mut segments = Mat::<f32>::zeros(segment_length, n_segments * n_bands);
 
     for i in 0..n_segments {
         let mut segments_slice = segments.subcols_mut(i * n_bands, n_bands);
-        let bands_slice = x_bands.subrows(i, SEGMENT_LENGTH);
+        let bands_slice = x_bands.subrows(i, segment_length);
         segments_slice.copy_from(bands_slice);
     }
 
     segments
 }
+
+/// Raise the band energies of every segment to at least `floor_db` dB
+/// below the maximum band energy of the segment.
+/// Segments have shape (segment_length, n_segments * bands), as from `segments`.
+pub fn floor_segments(mut segments: MatMut<f32>, n_bands: usize, floor_db: f32) {
+    let ratio = 10.0_f32.powf(floor_db / 20.0);
+
+    for i in 0..segments.ncols() / n_bands {
+        let segment = segments.as_mut().subcols_mut(i * n_bands, n_bands);
+        let max = segment
+            .as_ref()
+            .col_iter()
+            .flat_map(|band| band.iter())
+            .fold(0.0_f32, |max, &energy| max.max(energy));
+        let floor = max * ratio;
+
+        segment
+            .col_iter_mut()
+            .flat_map(|band| band.iter_mut())
+            .filter(|energy| **energy < floor) // NaN energies are kept
+            .for_each(|energy| *energy = floor);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::noise;
+
+    #[test]
+    fn hann_windows_are_symmetric_for_both_parities() {
+        for frame_length in [256, 255] {
+            let window = hann(frame_length);
+            assert_eq!(window.nrows(), frame_length);
+            for n in 0..frame_length {
+                let mirrored = window[frame_length - 1 - n];
+                assert!(
+                    (window[n] - mirrored).abs() < 1e-6,
+                    "{frame_length} samples window is asymmetric at {n}"
+                );
+            }
+            // The zero endpoints are trimmed
+            assert!(window[0] > 0.0);
+        }
+
+        // Odd windows peak on their center sample, even ones on their two center samples
+        assert!((hann(255)[127] - 1.0).abs() < 1e-6);
+        assert!((hann(256)[127] - hann(256)[128]).abs() < 1e-6);
+        assert!(hann(256)[127] < 1.0);
+    }
+
+    #[test]
+    fn hysteresis_keeps_short_dips_within_speech() {
+        // Speech at 0dB with a 45dB dip, after a leading frame as quiet as the dip
+        let energies = col![-45.0, 0.0, 0.0, -45.0, 0.0, -80.0_f32];
+
+        let (mask, count) = compute_frame_mask(energies.as_ref(), None, None);
+        assert_eq!(mask, col![false, true, true, false, true, false]);
+        assert_eq!(count, 3);
+
+        // The dip is within 10dB of the threshold: it is kept inside speech only
+        let (mask, count) = compute_frame_mask(energies.as_ref(), Some(10.0), None);
+        assert_eq!(mask, col![false, true, true, true, true, false]);
+        assert_eq!(count, 4);
+    }
+
+    #[test]
+    fn valid_frames_are_overlap_added_around_silent_gaps() {
+        // Silent gaps remove runs of frames, whose neighbours are then overlap-added
+        let mut x = noise(10_000, 1);
+        x[2_000..3_000].fill(0.0);
+        x[6_000..6_300].fill(0.0);
+        let config = StoiConfig::default();
+        let frames = process_frames(&x[..], &x[..], &config, &FRAME_WINDOWS).unwrap();
+        assert!(frames.count < frames.total - 1);
+
+        // As the reference: overlap-add the windowed valid frames into a signal,
+        // including the discarded last one, then slice and window it again
+        let hann = &FRAME_WINDOWS.window;
+        let mut signal = vec![0.0; (frames.valid.len() - 1) * HOP_LENGTH + FRAME_LENGTH];
+        for (k, &index) in frames.valid.iter().enumerate() {
+            for n in 0..FRAME_LENGTH {
+                signal[k * HOP_LENGTH + n] += x[index * HOP_LENGTH + n] * hann[n];
+            }
+        }
+
+        let mut frame = [0.0; FRAME_LENGTH];
+        for k in 0..frames.count {
+            frames.read_x(k, &mut frame);
+            for n in 0..FRAME_LENGTH {
+                let expected = signal[k * HOP_LENGTH + n] * hann[n];
+                assert!((frame[n] - expected).abs() < 1e-6, "frame {k}, sample {n}");
+            }
+        }
+    }
+
+    #[test]
+    fn frame_counts_match_hand_computation_for_several_overlaps() {
+        let x = noise(10_000, 2);
+        // Frames start at multiples of the hop, up to 9743 = 10000 - 256 - 1
+        for (hop_length, expected) in [(64, 153), (128, 77), (192, 51)] {
+            assert_eq!(frame_count(x.len(), hop_length), expected, "{hop_length}");
+
+            let config = StoiConfig {
+                hop_length,
+                ..StoiConfig::default()
+            };
+            config.validate().unwrap();
+            let frames = process_frames(&x[..], &x[..], &config, &FRAME_WINDOWS).unwrap();
+            assert_eq!(frames.total, expected, "{hop_length}");
+        }
+
+        for hop_length in [0, FRAME_LENGTH + 1] {
+            let config = StoiConfig {
+                hop_length,
+                ..StoiConfig::default()
+            };
+            assert!(config.validate().is_err(), "{hop_length}");
+        }
+    }
+
+    #[test]
+    fn silent_and_nan_clean_signals_are_silent_errors() {
+        assert_eq!(
+            max_energy(col![f32::NAN, f32::NAN].as_ref()),
+            f32::NEG_INFINITY
+        );
+        assert_eq!(max_energy(col![f32::NAN, -3.0, -5.0].as_ref()), -3.0);
+
+        let y = noise(10_000, 3);
+        let config = StoiConfig::default();
+        for x in [vec![0.0; 10_000], vec![f32::NAN; 10_000]] {
+            assert!(matches!(
+                process_frames(&x[..], &y[..], &config, &FRAME_WINDOWS),
+                Err(StoiError::SilentSignal)
+            ));
+        }
+
+        // NaN frames are dropped from speech without affecting the threshold
+        let (mask, count) = compute_frame_mask(col![0.0, f32::NAN, -10.0].as_ref(), None, None);
+        assert_eq!((mask, count), (col![true, false, true], 2));
+    }
+
+    /// Signal stored in separate chunks of 1000 samples
+    struct Chunked(Vec<Vec<f32>>);
+
+    impl FrameSource for Chunked {
+        fn len(&self) -> usize {
+            self.0.iter().map(Vec::len).sum()
+        }
+
+        fn read(&self, start: usize, out: &mut [f32]) {
+            for (i, out) in out.iter_mut().enumerate() {
+                *out = self.0[(start + i) / 1000][(start + i) % 1000];
+            }
+        }
+    }
+
+    #[test]
+    fn chunked_sources_yield_the_frames_of_slices() {
+        // Silent gaps make frames read their overlap-added neighbours
+        let mut x = noise(20_500, 4);
+        x[5_000..6_000].fill(0.0);
+        let y = noise(20_500, 5);
+        let chunked = |signal: &[f32]| Chunked(signal.chunks(1000).map(<[f32]>::to_vec).collect());
+        let (x_chunks, y_chunks) = (chunked(&x), chunked(&y));
+        let config = StoiConfig::default();
+
+        let frames = process_frames(&x[..], &y[..], &config, &FRAME_WINDOWS).unwrap();
+        let chunked_frames = process_frames(&x_chunks, &y_chunks, &config, &FRAME_WINDOWS).unwrap();
+        assert_eq!(chunked_frames.mask(), frames.mask());
+
+        let (mut expected, mut frame) = ([0.0; FRAME_LENGTH], [0.0; FRAME_LENGTH]);
+        for k in 0..frames.count {
+            frames.read_x(k, &mut expected);
+            chunked_frames.read_x(k, &mut frame);
+            assert_eq!(frame, expected, "clean frame {k}");
+            frames.read_y(k, &mut expected);
+            chunked_frames.read_y(k, &mut frame);
+            assert_eq!(frame, expected, "processed frame {k}");
+        }
+
+        assert_eq!(
+            crate::stoi_sources(&x_chunks, &y_chunks, &config),
+            crate::stoi_sources(&x[..], &y[..], &config)
+        );
+        assert_eq!(
+            crate::stoi_sources(&x_chunks, &y_chunks, &config),
+            crate::stoi(&x, &y, crate::FS, false)
+        );
+    }
+
+    #[test]
+    fn band_floor_flattens_near_silent_bands() {
+        // A single segment of 30 frames with a loud band and a near-silent band,
+        // whose tiny fluctuations are inverted in the processed signal
+        let (loud, quiet) = (noise(30, 1), noise(30, 2));
+        let segment = |sign: f32| {
+            Mat::from_fn(30, 2, |frame, band| match band {
+                0 => 1.0 + loud[frame].abs(),
+                _ => 1e-5 * (1.0 + sign * quiet[frame]),
+            })
+        };
+        let correlations = |floor_db: Option<f32>| {
+            let (mut x, mut y) = (segment(1.0), segment(-1.0));
+            if let Some(floor_db) = floor_db {
+                floor_segments(x.as_mut(), 2, floor_db);
+                floor_segments(y.as_mut(), 2, floor_db);
+            }
+            crate::standard::from_segments(
+                x.as_mut(),
+                y.as_mut(),
+                2,
+                -15.0,
+                crate::config::CorrelationKind::Pearson,
+            )
+        };
+
+        // The inaudible fluctuations dominate the correlation of the quiet band
+        let unfloored = correlations(None);
+        assert!(unfloored[(1, 0)] < -0.5, "{unfloored:?}");
+
+        // Floored at -40dB, the quiet band is flat and no longer correlated
+        let floored = correlations(Some(-40.0));
+        assert!(floored[(1, 0)].abs() < 0.05, "{floored:?}");
+        assert_eq!(floored[(0, 0)], unfloored[(0, 0)]);
+    }
+
+    #[test]
+    fn lazy_frames_are_the_frames_of_the_computation() {
+        let mut x = noise(10_000, 1);
+        x[3_000..6_000].fill(0.0);
+        let config = StoiConfig::default();
+        let windows = frame_windows(config.frame_window);
+
+        let frames: Vec<Vec<f32>> = frames_iter(&x, FRAME_LENGTH, config.hop_length).collect();
+        let valid = process_frames(x.as_slice(), x.as_slice(), &config, windows).unwrap();
+        assert_eq!(frames.len(), valid.total);
+
+        // Lazy frames are windowed slices, with the energies used to remove silent frames
+        for (i, frame) in frames.iter().enumerate() {
+            let start = i * config.hop_length;
+            let energy = frame_energy(
+                ColRef::from_slice(&x[start..start + FRAME_LENGTH]),
+                windows.window.as_ref(),
+            );
+            let norm = frame.iter().map(|x| x * x).sum::<f32>().sqrt();
+            let expected = 20.0 * (norm + f32::EPSILON).log10();
+            assert!(
+                (energy - expected).abs() < 1e-4,
+                "frame {i}: {energy} vs {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn absolute_references_depend_on_the_loudness() {
+        let loud = noise(10_000, 1);
+        let quiet: Vec<f32> = loud.iter().map(|x| 1e-3 * x).collect();
+        let kept = |x: &[f32], config: &StoiConfig| {
+            vad_trace(x, config)
+                .iter()
+                .filter(|(kept, _)| *kept)
+                .count()
+        };
+        let frames = frame_count(loud.len(), HOP_LENGTH);
+
+        // Relative to the maximum frame energy, silence is loudness independent
+        let relative = StoiConfig::default();
+        assert_eq!(kept(&loud, &relative), frames);
+        assert_eq!(kept(&quiet, &relative), frames);
+
+        // Noise frames have energies of about 15dB, and 60dB less once quiet
+        let absolute = StoiConfig {
+            vad_reference_db: Some(15.0),
+            ..StoiConfig::default()
+        };
+        assert_eq!(kept(&loud, &absolute), frames);
+        assert_eq!(kept(&quiet, &absolute), 0);
+    }
+
+    #[test]
+    fn normalized_references_are_raised_by_the_window_energy() {
+        let x = noise(10_000, 1);
+        let config = |vad_reference_db, vad_normalize_window| StoiConfig {
+            vad_reference_db,
+            vad_normalize_window,
+            ..StoiConfig::default()
+        };
+        // 10 * log10(sum(w²)) of the trimmed hann window
+        let window_db = 19.84;
+
+        // Relative thresholds do not depend on the normalization
+        assert_eq!(
+            vad_trace(&x, &config(None, false)),
+            vad_trace(&x, &config(None, true))
+        );
+
+        let windowed = vad_trace(&x, &config(Some(0.0), false));
+        let normalized = vad_trace(&x, &config(Some(0.0), true));
+        for ((_, windowed), (_, normalized)) in windowed.iter().zip(&normalized) {
+            assert!((windowed - normalized - window_db).abs() < 1e-2);
+        }
+
+        // Noise frames of about 15dB are above both thresholds of -40dB and -20dB,
+        // but only above the windowed threshold of a 40dB reference
+        assert!(windowed.iter().all(|(kept, _)| *kept));
+        assert!(normalized.iter().all(|(kept, _)| *kept));
+        assert!(
+            vad_trace(&x, &config(Some(40.0), false))
+                .iter()
+                .all(|(kept, _)| *kept)
+        );
+        assert!(
+            vad_trace(&x, &config(Some(40.0), true))
+                .iter()
+                .all(|(kept, _)| !kept)
+        );
+    }
+
+    #[test]
+    fn bartlett_and_welch_windows_have_their_shapes() {
+        let assert_close = |window: Col<f32>, expected: &[f32]| {
+            assert_eq!(window.nrows(), expected.len());
+            for (w, e) in window.iter().zip(expected) {
+                assert!((w - e).abs() < 1e-6, "{w} vs {e}");
+            }
+        };
+        // np.bartlett(6)[1:-1]
+        assert_close(bartlett(4), &[0.4, 0.8, 0.8, 0.4]);
+        assert_close(welch(4), &[0.64, 0.96, 0.96, 0.64]);
+
+        for frame_window in [FrameWindow::Hann, FrameWindow::Bartlett, FrameWindow::Welch] {
+            let window = frame_window.coefficients();
+            assert_eq!(window.len(), FRAME_LENGTH);
+            assert!(window.iter().all(|&w| w > 0.0 && w < 1.0));
+            for (a, b) in window.iter().zip(window.iter().rev()) {
+                assert!((a - b).abs() < 1e-6, "{frame_window:?}: {a} vs {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn every_frame_window_scores_end_to_end() {
+        let x = noise(30_000, 1);
+        let y = crate::test_signals::noisy(&x, 1.0, 2);
+        let hann = crate::stoi(&x, &y, 16_000, false).unwrap();
+
+        for frame_window in [FrameWindow::Bartlett, FrameWindow::Welch] {
+            for extended in [false, true] {
+                let config = StoiConfig {
+                    extended,
+                    frame_window,
+                    ..StoiConfig::default()
+                };
+                let score = crate::stoi_with_config(&x, &y, 16_000, &config).unwrap();
+                assert!(score > 0.0 && score < 1.0, "{frame_window:?}: {score}");
+                let clean = crate::stoi_with_config(&x, &x, 16_000, &config).unwrap();
+                assert!((clean - 1.0).abs() < 1e-5, "{frame_window:?}: {clean}");
+                if !extended {
+                    assert_ne!(score, hann, "{frame_window:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn partial_policies_complete_the_trailing_samples() {
+        // 6 frames up to sample 896, and 104 trailing samples
+        let x = noise(1_000, 1);
+        assert_eq!(frame_count(x.len(), HOP_LENGTH), 6);
+
+        let dropped = pad_partial(&x, HOP_LENGTH, PartialPolicy::Drop);
+        assert!(matches!(dropped, Cow::Borrowed(_)));
+
+        // A 7th frame from sample 768 covers the last sample
+        let zeros = pad_partial(&x, HOP_LENGTH, PartialPolicy::ZeroPad);
+        assert_eq!(zeros.len(), 768 + FRAME_LENGTH + 1);
+        assert_eq!(frame_count(zeros.len(), HOP_LENGTH), 7);
+        assert_eq!(zeros[..1_000], x);
+        assert!(zeros[1_000..].iter().all(|&sample| sample == 0.0));
+
+        let reflected = pad_partial(&x, HOP_LENGTH, PartialPolicy::Reflect);
+        assert_eq!(reflected.len(), zeros.len());
+        assert_eq!(reflected[..1_000], x);
+        for (i, &sample) in reflected[1_000..].iter().enumerate() {
+            assert_eq!(sample, x[998 - i]);
+        }
+    }
+
+    #[test]
+    fn every_partial_policy_scores_end_to_end() {
+        let x = noise(30_050, 2);
+        let y = crate::test_signals::noisy(&x, 1.0, 3);
+        let score = |include_partial| {
+            let config = StoiConfig {
+                include_partial,
+                ..StoiConfig::default()
+            };
+            crate::stoi_with_config(&x, &y, 10_000, &config).unwrap()
+        };
+
+        let dropped = score(PartialPolicy::Drop);
+        assert_eq!(dropped, crate::stoi(&x, &y, 10_000, false).unwrap());
+        for policy in [PartialPolicy::ZeroPad, PartialPolicy::Reflect] {
+            let padded = score(policy);
+            // The padded frame brings one more valid frame into the score
+            assert_ne!(padded, dropped, "{policy:?}");
+            assert!(
+                (padded - dropped).abs() < 1e-2,
+                "{policy:?}: {padded} vs {dropped}"
+            );
+        }
+    }
+
+    #[test]
+    fn zero_frame_or_hop_lengths_yield_no_frames() {
+        let x = noise(1_000, 1);
+        assert_eq!(frames_iter(&x, 0, 128).count(), 0);
+        assert_eq!(frames_iter(&x, 256, 0).count(), 0);
+        assert_eq!(frames_iter(&x, 256, 128).count(), 6);
+    }
+}
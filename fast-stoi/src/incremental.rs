@@ -0,0 +1,132 @@
+//! Incremental scoring of signals arriving in chunks
+
+use crate::{
+    config::StoiConfig,
+    engine::StoiEngine,
+    errors::{Result, StoiError},
+    pipeline,
+};
+
+/// STOI scorer accumulating clean and processed chunks, scored once complete.
+///
+/// [`IncrementalStoi::finalize`] yields exactly the score of [`stoi_with_config`]
+/// on the concatenated signals, whatever the chunk sizes.
+///
+/// This requires buffering both signals entirely: silent frames are removed
+/// relative to the most energetic frame of the whole clean signal, and the
+/// correlations of a segment depend on which frames are removed before it,
+/// so no frame can be scored before the last chunk. Chunks are buffered at
+/// their original sampling frequency and resampled at once in `finalize`,
+/// which keeps the resampling continuous across chunk boundaries. The memory
+/// used is thus the same as a one-shot computation on concatenated signals,
+/// and `push` only copies the chunks.
+///
+/// [`stoi_with_config`]: crate::stoi_with_config
+#[derive(Debug, Clone)]
+pub struct IncrementalStoi {
+    engine: StoiEngine,
+    x: Vec<f32>,
+    y: Vec<f32>,
+}
+
+impl IncrementalStoi {
+    /// Create a scorer for signals sampled at `fs_sig`, as [`StoiEngine::new`].
+    pub fn new(fs_sig: usize, config: StoiConfig) -> Result<Self> {
+        Ok(Self {
+            engine: StoiEngine::new(fs_sig, config)?,
+            x: Vec::new(),
+            y: Vec::new(),
+        })
+    }
+
+    /// Append a chunk of both signals.
+    ///
+    /// Returns [`StoiError::LengthMismatch`] if the chunks have different lengths,
+    /// and [`StoiError::SignalTooLong`] once the signals exceed the maximum
+    /// length of the configuration. Rejected chunks are not appended.
+    ///
+    /// Args:
+    /// * `clean_chunk` - Next samples of the clean speech signal
+    /// * `proc_chunk` - Next samples of the processed speech signal
+    pub fn push(&mut self, clean_chunk: &[f32], proc_chunk: &[f32]) -> Result<()> {
+        if clean_chunk.len() != proc_chunk.len() {
+            return Err(StoiError::LengthMismatch {
+                x_len: clean_chunk.len(),
+                y_len: proc_chunk.len(),
+            });
+        }
+        pipeline::check_length(
+            self.x.len() + clean_chunk.len(),
+            self.engine.fs_sig(),
+            self.engine.config(),
+        )?;
+
+        self.x.extend_from_slice(clean_chunk);
+        self.y.extend_from_slice(proc_chunk);
+        Ok(())
+    }
+
+    /// Amount of samples of each signal pushed so far
+    pub fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    /// Whether no samples were pushed yet
+    pub fn is_empty(&self) -> bool {
+        self.x.is_empty()
+    }
+
+    /// Compute the STOI measure of all the pushed chunks.
+    pub fn finalize(self) -> Result<f32> {
+        self.engine.score(&self.x, &self.y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{noise, noisy};
+
+    #[test]
+    fn chunked_scores_match_one_shot_scores() {
+        for (fs, extended) in [(10_000, false), (16_000, true), (44_100, false)] {
+            let x = noise(2 * fs, 1);
+            let y = noisy(&x, 1.0, 2);
+            let config = StoiConfig {
+                extended,
+                ..StoiConfig::default()
+            };
+            let expected = crate::stoi_with_config(&x, &y, fs, &config).unwrap();
+
+            for chunk in [1, 7, 160, 12_345] {
+                let mut scorer = IncrementalStoi::new(fs, config.clone()).unwrap();
+                x.chunks(chunk)
+                    .zip(y.chunks(chunk))
+                    .for_each(|(x, y)| scorer.push(x, y).unwrap());
+                assert_eq!(scorer.len(), x.len());
+                assert_eq!(scorer.finalize().unwrap(), expected, "{fs}Hz, {chunk}");
+            }
+        }
+    }
+
+    #[test]
+    fn rejected_chunks_are_not_appended() {
+        let config = StoiConfig {
+            max_signal_len: Some(1_000),
+            ..StoiConfig::default()
+        };
+        let mut scorer = IncrementalStoi::new(10_000, config).unwrap();
+        assert!(scorer.is_empty());
+
+        assert!(matches!(
+            scorer.push(&[0.0; 3], &[0.0; 2]),
+            Err(StoiError::LengthMismatch { x_len: 3, y_len: 2 })
+        ));
+        scorer.push(&[0.0; 600], &[0.0; 600]).unwrap();
+        assert!(matches!(
+            scorer.push(&[0.0; 600], &[0.0; 600]),
+            Err(StoiError::SignalTooLong { .. })
+        ));
+        assert_eq!(scorer.len(), 600);
+    }
+}
@@ -0,0 +1,64 @@
+//! Mapping from STOI scores to predicted intelligibility
+
+/// Parameters of the logistic mapping `100 / (1 + exp(a * d + b))`
+/// from a STOI score `d` to an intelligibility percentage.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogisticParams {
+    pub a: f32,
+    pub b: f32,
+}
+
+impl Default for LogisticParams {
+    /// Parameters fitted by Taal et al. (2011) on the IEEE English sentences
+    /// listening test data from Kjems et al. (2009).
+    fn default() -> Self {
+        Self {
+            a: -17.4906,
+            b: 9.6921,
+        }
+    }
+}
+
+/// Map a STOI score to a predicted intelligibility percentage in `[0, 100]`.
+///
+/// The mapping depends on the speech material and listening conditions.
+/// When `params` is `None`, the parameters from Taal et al. (2011),
+/// "An Algorithm for Intelligibility Prediction of Time-Frequency Weighted
+/// Noisy Speech", are used (`a = -17.4906`, `b = 9.6921`).
+pub fn stoi_to_intelligibility(score: f32, params: Option<LogisticParams>) -> f32 {
+    let LogisticParams { a, b } = params.unwrap_or_default();
+
+    100.0 / (1.0 + (a * score + b).exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_mapping_matches_known_anchor_points() {
+        let anchors = [
+            (0.0, 0.006_177),
+            (9.6921 / 17.4906, 50.0),
+            (0.75, 96.850),
+            (1.0, 99.959),
+        ];
+        for (score, expected) in anchors {
+            let intelligibility = stoi_to_intelligibility(score, None);
+            assert!(
+                (intelligibility - expected).abs() < 1e-3,
+                "{score}: {intelligibility} vs {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn custom_parameters_are_used() {
+        let params = LogisticParams { a: -10.0, b: 5.0 };
+        assert!((stoi_to_intelligibility(0.5, Some(params)) - 50.0).abs() < 1e-4);
+        assert_eq!(
+            stoi_to_intelligibility(0.5, Some(LogisticParams::default())),
+            stoi_to_intelligibility(0.5, None)
+        );
+    }
+}
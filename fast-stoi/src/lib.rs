@@ -1,73 +1,1364 @@
 //! Rust STOI implementation
+//!
+//! Scoring functions never panic on the contents of the signals they are given:
+//! empty, short, silent or mismatched signals, unsupported sampling frequencies
+//! and invalid configurations are all reported as [`StoiError`]s.
+//! Only allocation failures on huge signals can abort a computation.
+//! NaN samples are handled according to [`NanPolicy`].
 
+#[cfg(feature = "bench")]
+pub mod bench;
+mod config;
 mod constants;
+mod correlation;
+pub mod debug;
+mod engine;
 mod errors;
+mod estimate;
 mod extended;
+mod fft_resample;
 mod frames;
+mod incremental;
+mod intelligibility;
+mod mel;
+#[cfg(feature = "debug-npy")]
+mod npy;
 mod octave;
+mod pcm;
+mod pipeline;
+#[cfg(feature = "serde")]
+mod record;
+#[cfg(feature = "reference")]
+pub mod reference;
+mod report;
 mod resample;
+mod reverb;
+mod score_cache;
 mod standard;
 mod stft;
+#[cfg(test)]
+mod test_signals;
 mod upfirdn;
+#[cfg(feature = "viz")]
+mod viz;
+#[cfg(feature = "wav")]
+mod wav;
 
-use crate::{
-    constants::{FS, SEGMENT_LENGTH},
-    errors::{NotEnoughFramesError, Result},
+pub use crate::{
+    config::{BandScheme, CorrelationKind, FrameWindow, NanPolicy, PartialPolicy, StoiConfig},
+    constants::FS,
+    correlation::{pearson, spearman},
+    engine::StoiEngine,
+    errors::{Result, Stage, StoiError},
+    estimate::{estimate_memory, estimate_segments, min_signal_len},
+    fft_resample::resample_fft,
+    frames::{FrameSource, frames_iter},
+    incremental::IncrementalStoi,
+    intelligibility::{LogisticParams, stoi_to_intelligibility},
+    mel::mel_filterbank,
+    octave::{band_center_freqs, octave_band_energy, octave_bands, octave_filterbank},
+    pcm::{deinterleave, downmix, interleave, normalize_pcm_i16, normalize_pcm_i24},
+    report::StoiReport,
+    resample::{
+        CacheStats, ResampleMethod, ResamplePadding, ResampleQuality, resample,
+        resample_cache_metrics, resample_cache_stats, resample_exact, resample_no_cache,
+        resample_padded, resample_polyphase, resample_with, reset_resample_cache_metrics,
+        supported_sample_rates, try_resample,
+    },
+    reverb::{early_reflections, stoi_early_reflection},
+    score_cache::{clear_score_cache, stoi_cached},
 };
 
-/// Do the full computation post resampling to 10kHz
-fn compute(x: &[f32], y: &[f32], extended: bool) -> Result<f32> {
-    // Compute frames
-    let (x_frames, y_frames, mask, count) = frames::process_frames(x, y);
+#[cfg(feature = "debug-npy")]
+pub use crate::npy::stoi_dump;
+#[cfg(feature = "serde")]
+pub use crate::record::{ScoreRecord, stoi_record};
+#[cfg(feature = "viz")]
+pub use crate::viz::spectrogram_rgba;
+#[cfg(feature = "wav")]
+pub use crate::wav::{read_wav, read_wav_bytes, score_dir, stoi_wav_files};
 
-    if count < SEGMENT_LENGTH {
-        return Err(NotEnoughFramesError);
+/// Compute the Short-Time Objective Intelligibility (STOI) measure between two signals.
+///
+/// Args:
+/// * `x` - Clean speech signal
+/// * `y` - Processed speech signal
+/// * `fs_sig` - Sampling frequency of the signals
+/// * `extended` - Whether to use the extended STOI measure
+///
+/// Signals are any contiguous slice of samples: `&[f32]`, `&[f32; N]`, `Vec<f32>`
+/// or `Box<[f32]>`, borrowed or not. They are never copied when already at 10kHz.
+///
+/// Empty signals always return [`StoiError::SignalTooShort`], never a score.
+///
+/// Signals must be time-aligned: the crate has no alignment step, and detects
+/// neither delays nor polarity inversions. Their polarity does not matter anyway:
+/// only the spectrogram magnitudes are compared, so that inverting either signal
+/// yields exactly the same score.
+#[must_use = "the STOI computation may fail"]
+pub fn stoi(
+    x: impl AsRef<[f32]>,
+    y: impl AsRef<[f32]>,
+    fs_sig: usize,
+    extended: bool,
+) -> Result<f32> {
+    let config = StoiConfig {
+        extended,
+        ..Default::default()
+    };
+
+    stoi_with_config(x, y, fs_sig, &config)
+}
+
+/// Compute the Short-Time Objective Intelligibility (STOI) measure between two signals
+/// with custom computation parameters.
+///
+/// Signals are any contiguous slice of samples, as for [`stoi`].
+///
+/// Args:
+/// * `x` - Clean speech signal
+/// * `y` - Processed speech signal
+/// * `fs_sig` - Sampling frequency of the signals
+/// * `config` - Computation parameters
+pub fn stoi_with_config(
+    x: impl AsRef<[f32]>,
+    y: impl AsRef<[f32]>,
+    fs_sig: usize,
+    config: &StoiConfig,
+) -> Result<f32> {
+    stoi_report(x, y, fs_sig, config).map(|report| report.score)
+}
+
+/// Compute the Short-Time Objective Intelligibility (STOI) measure between two signals,
+/// along with statistics about the amount of speech it was computed on.
+///
+/// Args:
+/// * `x` - Clean speech signal
+/// * `y` - Processed speech signal
+/// * `fs_sig` - Sampling frequency of the signals
+/// * `config` - Computation parameters
+pub fn stoi_report(
+    x: impl AsRef<[f32]>,
+    y: impl AsRef<[f32]>,
+    fs_sig: usize,
+    config: &StoiConfig,
+) -> Result<StoiReport> {
+    let correlations = pipeline::correlations(x.as_ref(), y.as_ref(), fs_sig, config)?;
+
+    Ok(StoiReport::new(
+        correlations.score(),
+        correlations.valid_frames,
+        correlations.total_frames,
+        config.hop_length,
+    ))
+}
+
+/// Compute the per-band Short-Time Objective Intelligibility (STOI) scores between two signals.
+///
+/// Returns one score per one-third octave band, with center frequencies
+/// given by [`band_center_freqs`]. Their mean is the STOI score.
+/// With [`BandScheme::Mel`], there is one score per mel band instead.
+///
+/// Args:
+/// * `x` - Clean speech signal
+/// * `y` - Processed speech signal
+/// * `fs_sig` - Sampling frequency of the signals
+/// * `config` - Computation parameters
+pub fn stoi_bands(
+    x: impl AsRef<[f32]>,
+    y: impl AsRef<[f32]>,
+    fs_sig: usize,
+    config: &StoiConfig,
+) -> Result<Vec<f32>> {
+    pipeline::correlations(x.as_ref(), y.as_ref(), fs_sig, config)
+        .map(|correlations| correlations.band_scores())
+}
+
+/// Compute the Short-Time Objective Intelligibility (STOI) measure between two
+/// interleaved multichannel signals (`[l0, r0, l1, r1, ...]`), as decoders output.
+///
+/// Both signals are deinterleaved and downmixed to mono with equal weights
+/// (see [`deinterleave`] and [`downmix`]) before scoring.
+/// Returns [`StoiError::InvalidConfig`] if there are no channels, or if
+/// the length of a signal is not a multiple of the amount of channels.
+///
+/// Args:
+/// * `x` - Clean interleaved speech signal
+/// * `y` - Processed interleaved speech signal
+/// * `fs_sig` - Sampling frequency of the signals
+/// * `channels` - Amount of interleaved channels
+/// * `config` - Computation parameters
+pub fn stoi_interleaved(
+    x: &[f32],
+    y: &[f32],
+    fs_sig: usize,
+    channels: usize,
+    config: &StoiConfig,
+) -> Result<f32> {
+    if channels == 0 {
+        return Err(StoiError::InvalidConfig(
+            "channels must be strictly positive",
+        ));
+    }
+    if !x.len().is_multiple_of(channels) || !y.len().is_multiple_of(channels) {
+        return Err(StoiError::InvalidConfig(
+            "interleaved length must be a multiple of channels",
+        ));
     }
 
-    // Compute spectrograms
-    let x_spec = stft::compute_frame_rffts(x_frames.as_ref(), mask.as_ref(), count);
-    let y_spec = stft::compute_frame_rffts(y_frames.as_ref(), mask.as_ref(), count);
+    let to_mono = |data: &[f32]| {
+        let channels = deinterleave(data, channels);
+        let channels: Vec<&[f32]> = channels.iter().map(Vec::as_slice).collect();
+        downmix(&channels, None)
+    };
 
-    // Accumulate into octave bands
-    let x_bands = octave::compute_octave_bands(x_spec.as_ref());
-    let y_bands = octave::compute_octave_bands(y_spec.as_ref());
+    stoi_with_config(to_mono(x), to_mono(y), fs_sig, config)
+}
+
+/// Compute the Short-Time Objective Intelligibility (STOI) measure on the
+/// `[start_sec, end_sec]` time region of two signals.
+///
+/// The signals are sliced at their original sampling frequency, before resampling.
+///
+/// Args:
+/// * `x` - Clean speech signal
+/// * `y` - Processed speech signal
+/// * `fs_sig` - Sampling frequency of the signals
+/// * `config` - Computation parameters
+/// * `start_sec` - Start of the region in seconds
+/// * `end_sec` - End of the region in seconds
+pub fn stoi_region(
+    x: &[f32],
+    y: &[f32],
+    fs_sig: usize,
+    config: &StoiConfig,
+    start_sec: f32,
+    end_sec: f32,
+) -> Result<f32> {
+    if !(0.0 <= start_sec && start_sec < end_sec) {
+        return Err(StoiError::InvalidRegion);
+    }
 
-    // Slice into segments
-    let mut x_segments = frames::segments(x_bands.transpose());
-    let mut y_segments = frames::segments(y_bands.transpose());
+    let start = (start_sec * fs_sig as f32).round() as usize;
+    let end = (end_sec * fs_sig as f32).round() as usize;
 
-    if extended {
-        Ok(extended::from_segments(
-            x_segments.as_mut(),
-            y_segments.as_mut(),
-        ))
-    } else {
-        Ok(standard::from_segments(
-            x_segments.as_mut(),
-            y_segments.as_mut(),
-        ))
+    if end > x.len().min(y.len()) {
+        return Err(StoiError::InvalidRegion);
+    }
+    if !config.pad_short && end - start < min_signal_len(fs_sig, config) {
+        return Err(pipeline::too_short(end - start, fs_sig, config));
     }
+
+    stoi_with_config(&x[start..end], &y[start..end], fs_sig, config)
 }
 
-/// Compute the Short-Time Objective Intelligibility (STOI) measure between two signals.
+/// Compute the Short-Time Objective Intelligibility (STOI) measure of a processed signal
+/// against several valid clean references, keeping the best score.
+///
+/// The processed signal is resampled only once for all references.
+///
+/// Returns the best score along with the index of the reference achieving it,
+/// and [`StoiError::InvalidConfig`] if there are no references.
+///
+/// Args:
+/// * `refs` - Clean speech signals, all with the same length as `y`
+/// * `y` - Processed speech signal
+/// * `fs_sig` - Sampling frequency of the signals
+/// * `config` - Computation parameters
+pub fn stoi_multi_ref(
+    refs: &[&[f32]],
+    y: &[f32],
+    fs_sig: usize,
+    config: &StoiConfig,
+) -> Result<(f32, usize)> {
+    if refs.is_empty() {
+        return Err(StoiError::InvalidConfig(
+            "at least one reference is required",
+        ));
+    }
+
+    // Validate all references before any processing
+    for x in refs {
+        pipeline::check_inputs(x, y, fs_sig, config)?;
+    }
+
+    let y = pipeline::to_internal_rate(y, fs_sig, config);
+
+    let mut best = (f32::NEG_INFINITY, 0);
+    for (i, x) in refs.iter().enumerate() {
+        let x = pipeline::to_internal_rate(x, fs_sig, config);
+        let score = pipeline::compute(&x, &y, config)?.score();
+
+        if i == 0 || score > best.0 {
+            best = (score, i);
+        }
+    }
+
+    Ok(best)
+}
+
+/// Compute the Short-Time Objective Intelligibility (STOI) measure for a batch of
+/// (clean, processed) signal pairs.
+///
+/// Args:
+/// * `pairs` - Clean and processed speech signals
+/// * `fs_sig` - Sampling frequency of the signals
+/// * `config` - Computation parameters
+pub fn stoi_batch(
+    pairs: &[(&[f32], &[f32])],
+    fs_sig: usize,
+    config: &StoiConfig,
+) -> Vec<Result<f32>> {
+    stoi_batch_with_progress(pairs, fs_sig, config, |_| {})
+}
+
+/// Compute the Short-Time Objective Intelligibility (STOI) measure for a batch of
+/// (clean, processed) signal pairs, reporting progress after each pair.
+///
+/// `on_done` is called with the amount of pairs completed so far, after each pair.
+/// Pairs are processed sequentially on the calling thread, so the callback needs
+/// not be `Send` nor `Sync`. Callers processing batches in parallel should share
+/// an atomic counter between threads instead.
+///
+/// Args:
+/// * `pairs` - Clean and processed speech signals
+/// * `fs_sig` - Sampling frequency of the signals
+/// * `config` - Computation parameters
+/// * `on_done` - Progress callback
+pub fn stoi_batch_with_progress(
+    pairs: &[(&[f32], &[f32])],
+    fs_sig: usize,
+    config: &StoiConfig,
+    mut on_done: impl FnMut(usize),
+) -> Vec<Result<f32>> {
+    pairs
+        .iter()
+        .enumerate()
+        .map(|(i, (x, y))| {
+            let score = stoi_with_config(x, y, fs_sig, config);
+            on_done(i + 1);
+            score
+        })
+        .collect()
+}
+
+/// Select the `n` lowest scores of named batch results, for error analysis.
+///
+/// Returns the names and scores in increasing score order. Equal scores keep
+/// their order in `results`, so that the first results are selected first.
+/// Errors and NaN scores are excluded, and less than `n` pairs are returned
+/// if there are not enough valid scores.
+///
+/// The lowest scores are selected with a bounded heap, in `O(len * log(n))`
+/// time without sorting nor copying all results.
+pub fn worst_n(results: &[(String, Result<f32>)], n: usize) -> Vec<(String, f32)> {
+    /// Score ordered by value, then by position in the results
+    struct Ranked(f32, usize);
+
+    impl PartialEq for Ranked {
+        fn eq(&self, other: &Self) -> bool {
+            self.cmp(other) == std::cmp::Ordering::Equal
+        }
+    }
+    impl Eq for Ranked {}
+    impl PartialOrd for Ranked {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+    impl Ord for Ranked {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+        }
+    }
+
+    // Max-heap of the n lowest scores so far, whose top is the first to evict
+    let mut heap = std::collections::BinaryHeap::with_capacity(n + 1);
+    for (i, (_, score)) in results.iter().enumerate() {
+        let Ok(score) = *score else { continue };
+        if score.is_nan() {
+            continue;
+        }
+
+        heap.push(Ranked(score, i));
+        if heap.len() > n {
+            heap.pop();
+        }
+    }
+
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|Ranked(score, i)| (results[i].0.clone(), score))
+        .collect()
+}
+
+/// Compute the intermediate Short-Time Objective Intelligibility (STOI) correlations
+/// between two signals, for every segment and one-third octave band.
+///
+/// Returns a matrix with shape (num_segments, num_bands): rows are segments
+/// in time order, and columns are bands with center frequencies given by
+/// [`band_center_freqs`]. Its mean over both axes is the STOI score.
+/// With the extended measure, each value is the contribution of a segment band
+/// to the score rather than a correlation.
+///
+/// Args:
+/// * `x` - Clean speech signal
+/// * `y` - Processed speech signal
+/// * `fs_sig` - Sampling frequency of the signals
+/// * `config` - Computation parameters
+pub fn stoi_correlation_matrix(
+    x: &[f32],
+    y: &[f32],
+    fs_sig: usize,
+    config: &StoiConfig,
+) -> Result<faer::Mat<f32>> {
+    pipeline::correlations(x, y, fs_sig, config)
+        .map(|correlations| correlations.values.transpose().to_owned())
+}
+
+/// Compute the Short-Time Objective Intelligibility (STOI) measure between two signals
+/// with different sampling frequencies.
+///
+/// Each signal is resampled from its own sampling frequency to 10kHz.
+/// Both signals must span the same duration: after resampling, their lengths
+/// may only differ by one sample due to rounding, and the longest one is truncated.
+/// Otherwise, [`StoiError::LengthMismatch`] is returned with the lengths at 10kHz.
+///
+/// Args:
+/// * `x` - Clean speech signal
+/// * `fs_x` - Sampling frequency of the clean signal
+/// * `y` - Processed speech signal
+/// * `fs_y` - Sampling frequency of the processed signal
+/// * `config` - Computation parameters
+pub fn stoi_rates(
+    x: &[f32],
+    fs_x: usize,
+    y: &[f32],
+    fs_y: usize,
+    config: &StoiConfig,
+) -> Result<f32> {
+    pipeline::check_sample_rate(fs_x, config)?;
+    pipeline::check_sample_rate(fs_y, config)?;
+    if x.is_empty() {
+        return Err(pipeline::too_short(0, fs_x, config));
+    }
+    if y.is_empty() {
+        return Err(pipeline::too_short(0, fs_y, config));
+    }
+    config.validate()?;
+    pipeline::check_validated_rate(fs_x, config)?;
+    pipeline::check_validated_rate(fs_y, config)?;
+    pipeline::check_length(x.len(), fs_x, config)?;
+    pipeline::check_length(y.len(), fs_y, config)?;
+    if config.strict_clipping {
+        pipeline::check_clipping(x)?;
+        pipeline::check_clipping(y)?;
+    }
+
+    let x = pipeline::to_internal_rate(x, fs_x, config);
+    let y = pipeline::to_internal_rate(y, fs_y, config);
+
+    if x.len().abs_diff(y.len()) > 1 {
+        return Err(StoiError::LengthMismatch {
+            x_len: x.len(),
+            y_len: y.len(),
+        });
+    }
+    let len = x.len().min(y.len());
+
+    pipeline::compute(&x[..len], &y[..len], config).map(|correlations| correlations.score())
+}
+
+/// Compute both the standard and extended Short-Time Objective Intelligibility (STOI)
+/// measures between two signals, sharing all the processing before the correlations.
+///
+/// Returns the (standard, extended) scores. `config.extended` is ignored.
+///
+/// Args:
+/// * `x` - Clean speech signal
+/// * `y` - Processed speech signal
+/// * `fs_sig` - Sampling frequency of the signals
+/// * `config` - Computation parameters
+pub fn stoi_both(x: &[f32], y: &[f32], fs_sig: usize, config: &StoiConfig) -> Result<(f32, f32)> {
+    pipeline::check_inputs(x, y, fs_sig, config)?;
+
+    let x = pipeline::to_internal_rate(x, fs_sig, config);
+    let y = pipeline::to_internal_rate(y, fs_sig, config);
+
+    let (standard, extended) = pipeline::compute_both(&x, &y, config)?;
+    Ok((standard.score(), extended.score()))
+}
+
+/// Compute the Short-Time Objective Intelligibility (STOI) measure between two signals,
+/// relative to the score of the clean signal against itself.
+///
+/// Returns `stoi(x, y) / stoi(x, x)`: the clean self-score is the achievable maximum,
+/// which rounding errors may put marginally away from 1 (~1e-6 with the extended
+/// measure, usually below 1). Relative scores are thus typically marginally higher
+/// than standard scores, and exactly 1 for identical signals.
+/// The clean signal is only resampled once.
 ///
 /// Args:
 /// * `x` - Clean speech signal
 /// * `y` - Processed speech signal
 /// * `fs_sig` - Sampling frequency of the signals
 /// * `extended` - Whether to use the extended STOI measure
-pub fn stoi(x: &[f32], y: &[f32], fs_sig: usize, extended: bool) -> Result<f32> {
-    assert!(
-        x.len() == y.len(),
-        "Input signals must have the same length"
-    );
+pub fn stoi_relative(x: &[f32], y: &[f32], fs_sig: usize, extended: bool) -> Result<f32> {
+    let config = StoiConfig {
+        extended,
+        ..Default::default()
+    };
+    pipeline::check_inputs(x, y, fs_sig, &config)?;
+
+    let x = pipeline::to_internal_rate(x, fs_sig, &config);
+    let y = pipeline::to_internal_rate(y, fs_sig, &config);
+
+    let score = pipeline::compute(&x, &y, &config)?.score();
+    let self_score = pipeline::compute(&x, &x, &config)?.score();
+    Ok(score / self_score)
+}
+
+/// Compute the Short-Time Objective Intelligibility (STOI) measure between two signals
+/// read from [`FrameSource`]s, without requiring them to be contiguous in memory.
+///
+/// The sources must already be sampled at 10kHz, as they are not resampled.
+/// `config.trim_silence`, `config.normalize_rms`, `config.strict_clipping` and
+/// `config.include_partial` are ignored.
+///
+/// Args:
+/// * `x` - Clean speech signal source
+/// * `y` - Processed speech signal source
+/// * `config` - Computation parameters
+pub fn stoi_sources<S: FrameSource + ?Sized>(x: &S, y: &S, config: &StoiConfig) -> Result<f32> {
+    if x.is_empty() || y.is_empty() {
+        return Err(pipeline::too_short(0, constants::FS, config));
+    }
+    if x.len() != y.len() {
+        return Err(StoiError::LengthMismatch {
+            x_len: x.len(),
+            y_len: y.len(),
+        });
+    }
+    config.validate()?;
+    pipeline::check_length(x.len(), constants::FS, config)?;
+
+    pipeline::compute_from_sources(x, y, config).map(|correlations| correlations.score())
+}
+
+/// Compute the Short-Time Objective Intelligibility (STOI) measure between two signals,
+/// with a custom frame window instead of the trimmed hann window of the reference.
+///
+/// The window is applied both when removing silent frames and before the spectrograms,
+/// as the hann window would be, for parity with tools using another taper.
+/// Returns [`StoiError::InvalidConfig`] if the window does not have 256 samples (one frame).
+/// `config.frame_window` is ignored.
+///
+/// Args:
+/// * `x` - Clean speech signal
+/// * `y` - Processed speech signal
+/// * `fs_sig` - Sampling frequency of the signals
+/// * `config` - Computation parameters
+/// * `window` - Frame window of 256 samples
+pub fn stoi_with_window(
+    x: &[f32],
+    y: &[f32],
+    fs_sig: usize,
+    config: &StoiConfig,
+    window: &[f32],
+) -> Result<f32> {
+    if window.len() != constants::FRAME_LENGTH {
+        return Err(StoiError::InvalidConfig(
+            "window length must be the frame length",
+        ));
+    }
+    pipeline::check_inputs(x, y, fs_sig, config)?;
 
-    if fs_sig != FS {
-        let x = resample::resample(x, fs_sig, FS);
-        let y = resample::resample(y, fs_sig, FS);
+    let windows = frames::FrameWindows::new(faer::ColRef::from_slice(window).to_owned());
+    let x = pipeline::to_internal_rate(x, fs_sig, config);
+    let y = pipeline::to_internal_rate(y, fs_sig, config);
 
-        compute(&x, &y, extended)
+    pipeline::compute_with_windows(&x, &y, config, &windows)
+        .map(|correlations| correlations.score())
+}
+
+/// Valid frames of both signals, frame mask and amount of valid frames,
+/// as returned by [`resample_and_frame`]
+pub type ResampledFrames = (faer::Mat<f32>, faer::Mat<f32>, Vec<bool>, usize);
+
+/// Resample two signals to 10kHz and slice them into windowed frames,
+/// removing the silent frames: the front end of STOI before the spectrograms.
+///
+/// Returns the (x_frames, y_frames, mask, count) of the valid frames:
+/// * `x_frames`, `y_frames` - Valid frames with shape (256, count), one frame per
+///   column in time order, windowed as they enter the spectrograms
+///   (after overlap-adding the valid frames and slicing them again)
+/// * `mask` - Whether each of the frames of the signal is valid, of length the
+///   amount of frames before removing silent frames
+/// * `count` - Amount of valid frames, the amount of `true` values in `mask`
+///
+/// Silent signals return [`StoiError::SilentSignal`], but signals with less valid
+/// frames than a segment do not fail, as no segments are computed.
+///
+/// Args:
+/// * `x` - Clean speech signal
+/// * `y` - Processed speech signal
+/// * `fs_sig` - Sampling frequency of the signals
+/// * `config` - Computation parameters
+pub fn resample_and_frame(
+    x: &[f32],
+    y: &[f32],
+    fs_sig: usize,
+    config: &StoiConfig,
+) -> Result<ResampledFrames> {
+    pipeline::check_inputs(x, y, fs_sig, config)?;
+
+    let x = pipeline::to_internal_rate(x, fs_sig, config);
+    let y = pipeline::to_internal_rate(y, fs_sig, config);
+    let (x, y) = if config.trim_silence {
+        frames::trim_silence(&x, &y)
+    } else {
+        (&x[..], &y[..])
+    };
+    let (x, y) = if config.normalize_rms {
+        (frames::normalize_rms(x), frames::normalize_rms(y))
     } else {
-        compute(x, y, extended)
+        (std::borrow::Cow::Borrowed(x), std::borrow::Cow::Borrowed(y))
+    };
+    let x = frames::pad_partial(&x, config.hop_length, config.include_partial);
+    let y = frames::pad_partial(&y, config.hop_length, config.include_partial);
+
+    // At least one frame is needed to compute frame energies
+    if x.len() <= constants::FRAME_LENGTH {
+        return Err(pipeline::too_short(x.len(), constants::FS, config));
+    }
+
+    let frames =
+        frames::process_frames(&*x, &*y, config, frames::frame_windows(config.frame_window))?;
+    let mut x_frames = faer::Mat::zeros(constants::FRAME_LENGTH, frames.count);
+    let mut y_frames = faer::Mat::zeros(constants::FRAME_LENGTH, frames.count);
+    let mut frame = [0.0; constants::FRAME_LENGTH];
+    for k in 0..frames.count {
+        frames.read_x(k, &mut frame);
+        x_frames
+            .col_mut(k)
+            .copy_from(faer::ColRef::from_slice(&frame));
+        frames.read_y(k, &mut frame);
+        y_frames
+            .col_mut(k)
+            .copy_from(faer::ColRef::from_slice(&frame));
+    }
+
+    Ok((x_frames, y_frames, frames.mask(), frames.count))
+}
+
+/// Compute the squared magnitude spectrogram of windowed frames,
+/// such as the frames returned by [`resample_and_frame`].
+///
+/// Frames have shape (frame_length, count), one frame per column in time order,
+/// with at most 512 samples. They are zero padded to 512 samples as in STOI,
+/// and their spectrogram `|X|^2` has shape (257, count), as expected by
+/// [`octave_bands`].
+///
+/// Returns [`StoiError::InvalidConfig`] if frames have more than 512 samples.
+///
+/// Args:
+/// * `frames` - Windowed frames
+pub fn spectrogram(frames: faer::MatRef<f32>) -> Result<faer::Mat<f32>> {
+    if frames.nrows() > constants::FFT_LENGTH {
+        return Err(StoiError::InvalidConfig(
+            "frames length must be at most the FFT length",
+        ));
+    }
+
+    Ok(stft::compute_frame_rffts(
+        frames.ncols(),
+        frames.nrows(),
+        |k, out| {
+            out.iter_mut()
+                .zip(frames.col(k).iter())
+                .for_each(|(out, &sample)| *out = sample);
+        },
+    ))
+}
+
+/// Compute the log-magnitude spectrogram in dB of windowed frames,
+/// such as the frames returned by [`resample_and_frame`].
+///
+/// Frames have shape (frame_length, count), one frame per column in time order,
+/// with at most 512 samples. They are zero padded to 512 samples as in STOI,
+/// and their spectrogram `20 * log10(|X|)` has shape (257, count).
+/// Values below `floor_db` are raised to `floor_db`, which is applied after the
+/// magnitude so that zero bins yield `floor_db` instead of `-inf`.
+/// A NaN floor applies no floor.
+///
+/// Returns [`StoiError::InvalidConfig`] if frames have more than 512 samples.
+///
+/// Args:
+/// * `frames` - Windowed frames
+/// * `floor_db` - Minimum value of the spectrogram in dB
+pub fn log_spectrogram(frames: faer::MatRef<f32>, floor_db: f32) -> Result<faer::Mat<f32>> {
+    if frames.nrows() > constants::FFT_LENGTH {
+        return Err(StoiError::InvalidConfig(
+            "frames length must be at most the FFT length",
+        ));
+    }
+
+    Ok(stft::compute_frame_log_rffts(
+        frames.ncols(),
+        frames.nrows(),
+        |k, out| {
+            out.iter_mut()
+                .zip(frames.col(k).iter())
+                .for_each(|(out, &sample)| *out = sample);
+        },
+        floor_db,
+    ))
+}
+
+/// Compute the Short-Time Objective Intelligibility (STOI) measure from windowed
+/// valid frames at 10kHz, for custom framing and silent frame removal upstream.
+///
+/// Frames have shape (256, count), one frame per column in time order, as returned
+/// by [`resample_and_frame`]. They must already be windowed, as they are directly
+/// zero padded to 512 samples for the spectrograms: the reference windows frames
+/// with the hann window of [`FrameWindow::Hann`], then overlap-adds the valid
+/// frames and slices them again, which [`resample_and_frame`] does.
+///
+/// Frames may have any memory layout, such as transposed or strided views:
+/// they are copied sample by sample into the FFT input buffers.
+///
+/// Returns [`StoiError::InvalidConfig`] if frames do not have 256 samples,
+/// and [`StoiError::LengthMismatch`] with the amounts of frames if they differ.
+///
+/// Args:
+/// * `x_frames` - Clean speech frames
+/// * `y_frames` - Processed speech frames
+/// * `extended` - Whether to use the extended STOI measure
+pub fn stoi_from_frames(
+    x_frames: faer::MatRef<f32>,
+    y_frames: faer::MatRef<f32>,
+    extended: bool,
+) -> Result<f32> {
+    if x_frames.nrows() != constants::FRAME_LENGTH || y_frames.nrows() != constants::FRAME_LENGTH {
+        return Err(StoiError::InvalidConfig(
+            "frames length must be the frame length",
+        ));
+    }
+    if x_frames.ncols() != y_frames.ncols() {
+        return Err(StoiError::LengthMismatch {
+            x_len: x_frames.ncols(),
+            y_len: y_frames.ncols(),
+        });
+    }
+
+    let config = StoiConfig {
+        extended,
+        ..Default::default()
+    };
+    let read_frame = |frames: faer::MatRef<f32>, k: usize, out: &mut [f32]| {
+        out.iter_mut()
+            .zip(frames.col(k).iter())
+            .for_each(|(out, &sample)| *out = sample);
+    };
+
+    pipeline::compute_from_frames(
+        x_frames.ncols(),
+        |k, out| read_frame(x_frames, k, out),
+        |k, out| read_frame(y_frames, k, out),
+        &config,
+    )
+    .map(|correlations| correlations.score())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{noise, noisy};
+
+    #[test]
+    fn multi_ref_keeps_the_best_matching_reference() {
+        let x = noise(30_000, 1);
+        let y = noisy(&x, 0.5, 2);
+        let other = noise(30_000, 3);
+        let config = StoiConfig::default();
+
+        let (score, index) = stoi_multi_ref(&[&other, &x], &y, 16_000, &config).unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(score, stoi(&x, &y, 16_000, false).unwrap());
+        assert!(score > stoi(&other, &y, 16_000, false).unwrap() + 0.3);
+    }
+
+    #[test]
+    fn segment_length_sets_the_amount_of_segments() {
+        let x = noise(30_000, 1);
+        let y = noisy(&x, 1.0, 2);
+        let segments = |segment_frames| {
+            let config = StoiConfig {
+                segment_frames,
+                ..StoiConfig::default()
+            };
+            let matrix = stoi_correlation_matrix(&x, &y, 10_000, &config).unwrap();
+            (
+                matrix.nrows(),
+                stoi_with_config(&x, &y, 10_000, &config).unwrap(),
+            )
+        };
+
+        let (reference_segments, reference_score) = segments(30);
+        assert_eq!(reference_score, stoi(&x, &y, 10_000, false).unwrap());
+
+        // Every frame less in a segment adds a segment
+        let (short_segments, short_score) = segments(10);
+        assert_eq!(short_segments, reference_segments + 20);
+        assert_ne!(short_score, reference_score);
+        assert!((0.0..1.0).contains(&short_score));
+    }
+
+    #[test]
+    fn trimmed_segments_are_excluded_from_the_average() {
+        let x = noise(30_000, 1);
+        let y = noisy(&x, 1.0, 2);
+        let config = StoiConfig::default();
+        let matrix = stoi_correlation_matrix(&x, &y, 10_000, &config).unwrap();
+
+        let trimmed = StoiConfig {
+            trim_segments: 0,
+            ..config.clone()
+        };
+        assert_eq!(
+            stoi_with_config(&x, &y, 10_000, &trimmed).unwrap(),
+            stoi(&x, &y, 10_000, false).unwrap()
+        );
+
+        let trimmed = StoiConfig {
+            trim_segments: 20,
+            ..config
+        };
+        let kept = matrix.subrows(20, matrix.nrows() - 40);
+        let expected = kept.sum() / (kept.nrows() * kept.ncols()) as f32;
+        let score = stoi_with_config(&x, &y, 10_000, &trimmed).unwrap();
+        assert!((score - expected).abs() < 1e-5);
+        assert_eq!(
+            stoi_correlation_matrix(&x, &y, 10_000, &trimmed)
+                .unwrap()
+                .nrows(),
+            kept.nrows()
+        );
+    }
+
+    #[test]
+    fn trimmed_segments_need_more_valid_frames() {
+        let config = StoiConfig {
+            trim_segments: 5,
+            ..StoiConfig::default()
+        };
+        let len = min_signal_len(10_000, &config);
+        let x = noise(len, 1);
+        let y = noisy(&x, 1.0, 2);
+
+        assert!(stoi_with_config(&x, &y, 10_000, &config).is_ok());
+        assert_eq!(
+            stoi_with_config(&x[..len - 128], &y[..len - 128], 10_000, &config),
+            Err(StoiError::NotEnoughFrames {
+                valid_frames: 39,
+                segment_frames: 40,
+            })
+        );
+    }
+
+    #[test]
+    fn huge_lengths_and_rates_fail_without_allocating() {
+        let x = noise(30_000, 1);
+        let config = StoiConfig::default();
+
+        assert_eq!(
+            stoi(&x, &x, usize::MAX, false),
+            Err(StoiError::InvalidSampleRate(usize::MAX))
+        );
+        assert!(matches!(
+            pipeline::check_length(usize::MAX, 1, &config),
+            Err(StoiError::SignalTooLong {
+                len: usize::MAX,
+                ..
+            })
+        ));
+        let unbounded = StoiConfig {
+            max_signal_len: None,
+            ..config
+        };
+        assert!(matches!(
+            pipeline::check_length(constants::MAX_BUFFER_LEN + 1, constants::FS, &unbounded),
+            Err(StoiError::SignalTooLong { .. })
+        ));
+        assert_eq!(
+            pipeline::check_length(constants::MAX_BUFFER_LEN, constants::FS, &unbounded),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn multi_ref_rejects_empty_references() {
+        let y = noise(30_000, 1);
+
+        assert!(matches!(
+            stoi_multi_ref(&[], &y, 16_000, &StoiConfig::default()),
+            Err(StoiError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn region_scores_match_the_sliced_signals() {
+        let x = noise(48_000, 4);
+        let y = noisy(&x, 1.0, 5);
+        let config = StoiConfig::default();
+
+        // Sliced at 16kHz, before resampling
+        let region = stoi_region(&x, &y, 16_000, &config, 0.5, 2.5).unwrap();
+        let sliced = stoi_with_config(&x[8_000..40_000], &y[8_000..40_000], 16_000, &config);
+        assert_eq!(region, sliced.unwrap());
+
+        assert_eq!(
+            stoi_region(&x, &y, 16_000, &config, 2.0, 1.0),
+            Err(StoiError::InvalidRegion)
+        );
+        assert_eq!(
+            stoi_region(&x, &y, 16_000, &config, 0.0, 0.1),
+            Err(pipeline::too_short(1_600, 16_000, &config))
+        );
+    }
+
+    #[test]
+    fn empty_signals_are_too_short() {
+        let x = noise(30_000, 1);
+        let config = StoiConfig::default();
+        let too_short = Err(pipeline::too_short(0, 16_000, &config));
+
+        for extended in [false, true] {
+            assert_eq!(stoi([], [], 16_000, extended), too_short);
+            // One empty signal is reported as too short rather than mismatched
+            assert_eq!(stoi(&x, [], 16_000, extended), too_short);
+            assert_eq!(stoi([], &x, 16_000, extended), too_short);
+        }
+    }
+
+    #[test]
+    fn batch_progress_is_reported_once_per_pair() {
+        let x = noise(16_000, 1);
+        let y = noisy(&x, 1.0, 2);
+        let short = noise(100, 3);
+        // Failing pairs are reported too
+        let pairs: [(&[f32], &[f32]); 3] = [(&x, &y), (&short, &short), (&y, &x)];
+
+        let mut progress = Vec::new();
+        let scores = stoi_batch_with_progress(&pairs, 10_000, &StoiConfig::default(), |done| {
+            progress.push(done)
+        });
+
+        assert_eq!(progress, vec![1, 2, 3]);
+        assert_eq!(scores.len(), pairs.len());
+        assert!(scores[1].is_err());
+    }
+
+    #[test]
+    fn correlation_matrix_mean_is_the_score() {
+        let x = noise(30_000, 1);
+        let y = noisy(&x, 1.0, 2);
+
+        for config in [StoiConfig::reference(), StoiConfig::reference_extended()] {
+            let matrix = stoi_correlation_matrix(&x, &y, 16_000, &config).unwrap();
+            assert_eq!(matrix.ncols(), constants::NUM_BANDS);
+
+            let mean = matrix.sum() / (matrix.nrows() * matrix.ncols()) as f32;
+            let score = stoi_with_config(&x, &y, 16_000, &config).unwrap();
+            assert!((mean - score).abs() < 1e-6, "{mean} vs {score}");
+        }
+    }
+
+    #[test]
+    fn signals_at_different_rates_match_pre_resampled_signals() {
+        let x = noise(48_000, 1);
+        let y = resample(&noisy(&x, 1.0, 2), 16_000, 8_000);
+        let config = StoiConfig::default();
+
+        let score = stoi_rates(&x, 16_000, &y, 8_000, &config).unwrap();
+        let x_common = resample(&x, 16_000, constants::FS);
+        let y_common = resample(&y, 8_000, constants::FS);
+        let expected = stoi_with_config(&x_common, &y_common, constants::FS, &config).unwrap();
+        assert_eq!(score, expected);
+
+        // Signals of different durations are rejected
+        assert_eq!(
+            stoi_rates(&x, 16_000, &y[..20_000], 8_000, &config),
+            Err(StoiError::LengthMismatch {
+                x_len: 30_000,
+                y_len: 25_000,
+            })
+        );
+    }
+
+    #[test]
+    fn trimmed_scores_ignore_leading_and_trailing_silence() {
+        let x = noise(30_000, 1);
+        let y = noisy(&x, 1.0, 2);
+        let pad = |signal: &[f32]| [vec![0.0; 5_003], signal.to_vec(), vec![0.0; 7_001]].concat();
+        let config = StoiConfig {
+            trim_silence: true,
+            ..StoiConfig::default()
+        };
+
+        let score = stoi_with_config(&x, &y, 10_000, &config).unwrap();
+        assert_eq!(
+            stoi_with_config(pad(&x), pad(&y), 10_000, &config),
+            Ok(score)
+        );
+        assert_ne!(stoi(pad(&x), pad(&y), 10_000, false), Ok(score));
+
+        // Padding shifts the resampling phase at other rates
+        let score = stoi_with_config(&x, &y, 16_000, &config).unwrap();
+        let padded = stoi_with_config(pad(&x), pad(&y), 16_000, &config).unwrap();
+        assert!((padded - score).abs() < 1e-2, "{padded} vs {score}");
+    }
+
+    #[test]
+    fn both_measures_match_separate_calls() {
+        let x = noise(30_000, 1);
+        let y = noisy(&x, 1.0, 2);
+        let config = StoiConfig::default();
+
+        for fs in [10_000, 16_000] {
+            let (standard, extended) = stoi_both(&x, &y, fs, &config).unwrap();
+            assert_eq!(standard, stoi(&x, &y, fs, false).unwrap());
+            assert_eq!(extended, stoi(&x, &y, fs, true).unwrap());
+        }
+    }
+
+    #[test]
+    fn scores_support_other_band_counts() {
+        let x = noise(30_000, 1);
+        let y = noisy(&x, 1.0, 2);
+
+        for n_mels in [1, 8, 24] {
+            for extended in [false, true] {
+                let config = StoiConfig {
+                    extended,
+                    band_scheme: BandScheme::Mel { n_mels },
+                    ..StoiConfig::default()
+                };
+                let bands = stoi_bands(&x, &y, 10_000, &config).unwrap();
+                let matrix = stoi_correlation_matrix(&x, &y, 10_000, &config).unwrap();
+                let score = stoi_with_config(&x, &y, 10_000, &config).unwrap();
+
+                assert_eq!(bands.len(), n_mels);
+                assert_eq!(matrix.ncols(), n_mels);
+                let mean = bands.iter().sum::<f32>() / n_mels as f32;
+                assert!(
+                    (mean - score).abs() < 1e-6,
+                    "{n_mels} bands: {mean} vs {score}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn short_signals_score_when_padded() {
+        // 15 frames of 256 samples with a hop of 128 at 10kHz
+        let x = noise(14 * 128 + 256, 1);
+        let y = noisy(&x, 0.5, 2);
+        let config = StoiConfig {
+            pad_short: true,
+            ..StoiConfig::default()
+        };
+
+        assert!(stoi(&x, &y, 10_000, false).is_err());
+        let score = stoi_with_config(&x, &y, 10_000, &config).unwrap();
+        assert!(score.is_finite() && score > 0.0 && score < 1.0, "{score}");
+        let clean = stoi_with_config(&x, &x, 10_000, &config).unwrap();
+        assert!((clean - 1.0).abs() < 1e-5, "{clean}");
+    }
+
+    #[test]
+    fn custom_windows_replace_the_hann_window() {
+        let x = noise(30_000, 1);
+        let y = noisy(&x, 1.0, 2);
+        let config = StoiConfig::default();
+        let reference = stoi_with_config(&x, &y, 16_000, &config).unwrap();
+
+        let hann = frames::hann(constants::FRAME_LENGTH);
+        let hann: Vec<f32> = hann.iter().copied().collect();
+        let score = stoi_with_window(&x, &y, 16_000, &config, &hann).unwrap();
+        assert_eq!(score, reference);
+
+        // A rectangular window leaks energy across bands, changing the score
+        let rectangular = vec![1.0; constants::FRAME_LENGTH];
+        let score = stoi_with_window(&x, &y, 16_000, &config, &rectangular).unwrap();
+        assert!((score - reference).abs() > 1e-3, "{score} vs {reference}");
+        assert!(score > 0.0 && score < 1.0, "{score}");
+
+        assert!(stoi_with_window(&x, &y, 16_000, &config, &rectangular[1..]).is_err());
+    }
+
+    #[test]
+    fn reference_constructors_match_the_free_function_defaults() {
+        let x = noise(30_000, 1);
+        let y = noisy(&x, 1.0, 2);
+
+        assert_eq!(StoiConfig::reference(), StoiConfig::default());
+        assert!(StoiConfig::reference().is_reference());
+        assert!(StoiConfig::reference_extended().is_reference());
+        let custom = StoiConfig {
+            segment_frames: 20,
+            ..StoiConfig::reference()
+        };
+        assert!(!custom.is_reference());
+
+        for (config, extended) in [
+            (StoiConfig::reference(), false),
+            (StoiConfig::reference_extended(), true),
+        ] {
+            assert_eq!(
+                stoi_with_config(&x, &y, 16_000, &config).unwrap(),
+                stoi(&x, &y, 16_000, extended).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn every_input_type_gives_the_same_score() {
+        let x = noise(16_000, 1);
+        let y = noisy(&x, 1.0, 2);
+        let expected = stoi(x.as_slice(), y.as_slice(), 16_000, false).unwrap();
+
+        let x_array: [f32; 16_000] = x.clone().try_into().unwrap();
+        let y_array: [f32; 16_000] = y.clone().try_into().unwrap();
+        assert_eq!(stoi(&x, &y, 16_000, false), Ok(expected));
+        assert_eq!(stoi(x.clone(), y.clone(), 16_000, false), Ok(expected));
+        assert_eq!(stoi(x_array, y_array, 16_000, false), Ok(expected));
+        assert_eq!(
+            stoi(x.clone().into_boxed_slice(), &y, 16_000, false),
+            Ok(expected)
+        );
+    }
+
+    #[test]
+    fn combined_front_end_matches_the_separate_stages() {
+        let mut x = noise(32_000, 1);
+        x[10_000..20_000].fill(0.0);
+        let y = noisy(&x, 0.5, 2);
+        let config = StoiConfig::default();
+
+        let (x_frames, y_frames, mask, count) =
+            resample_and_frame(&x, &y, 16_000, &config).unwrap();
+
+        let x = resample(&x, 16_000, constants::FS);
+        let y = resample(&y, 16_000, constants::FS);
+        let frames =
+            frames::process_frames(x.as_slice(), y.as_slice(), &config, &frames::FRAME_WINDOWS)
+                .unwrap();
+        assert_eq!(count, frames.count);
+        assert_eq!(mask, frames.mask());
+        assert_eq!(mask.iter().filter(|&&valid| valid).count(), count);
+        assert!(count < frames.total);
+
+        let mut frame = [0.0; constants::FRAME_LENGTH];
+        for k in 0..count {
+            frames.read_x(k, &mut frame);
+            assert_eq!(x_frames.col(k), faer::ColRef::from_slice(&frame));
+            frames.read_y(k, &mut frame);
+            assert_eq!(y_frames.col(k), faer::ColRef::from_slice(&frame));
+        }
+    }
+
+    #[test]
+    fn relative_scores_of_identical_signals_are_exactly_one() {
+        let x = noise(30_000, 1);
+        let y = noisy(&x, 1.0, 2);
+
+        for extended in [false, true] {
+            assert_eq!(stoi_relative(&x, &x, 16_000, extended), Ok(1.0));
+
+            let relative = stoi_relative(&x, &y, 16_000, extended).unwrap();
+            let score = stoi(&x, &y, 16_000, extended).unwrap();
+            let self_score = stoi(&x, &x, 16_000, extended).unwrap();
+            assert_eq!(relative, score / self_score);
+        }
+    }
+
+    #[test]
+    fn worst_n_selects_the_lowest_scores_in_order() {
+        let results: Vec<(String, Result<f32>)> = [
+            ("a", Ok(0.9)),
+            ("b", Ok(0.3)),
+            ("c", Err(StoiError::SilentSignal)),
+            ("d", Ok(0.5)),
+            ("e", Ok(f32::NAN)),
+            ("f", Ok(0.3)),
+            ("g", Ok(0.1)),
+        ]
+        .into_iter()
+        .map(|(name, score)| (name.to_string(), score))
+        .collect();
+        let names = |n| {
+            worst_n(&results, n)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>()
+        };
+
+        // Equal scores keep their order in the results
+        assert_eq!(
+            worst_n(&results, 3),
+            vec![
+                ("g".to_string(), 0.1),
+                ("b".to_string(), 0.3),
+                ("f".to_string(), 0.3)
+            ]
+        );
+        assert_eq!(names(2), ["g", "b"]);
+        assert_eq!(names(0), Vec::<String>::new());
+        // Errors and NaN scores are never selected
+        assert_eq!(names(10), ["g", "b", "f", "d", "a"]);
+    }
+
+    #[test]
+    fn scores_from_valid_frames_match_the_full_computation() {
+        let mut x = noise(32_000, 1);
+        x[10_000..20_000].fill(0.0);
+        let y = noisy(&x, 0.5, 2);
+        let (x_frames, y_frames, _, count) =
+            resample_and_frame(&x, &y, 16_000, &StoiConfig::default()).unwrap();
+
+        for extended in [false, true] {
+            let score = stoi_from_frames(x_frames.as_ref(), y_frames.as_ref(), extended);
+            assert_eq!(score, stoi(&x, &y, 16_000, extended));
+        }
+
+        assert_eq!(
+            stoi_from_frames(x_frames.as_ref(), y_frames.subcols(1, count - 1), false),
+            Err(StoiError::LengthMismatch {
+                x_len: count,
+                y_len: count - 1,
+            })
+        );
+        assert!(matches!(
+            stoi_from_frames(x_frames.subrows(1, 255), y_frames.subrows(1, 255), false),
+            Err(StoiError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn interleaved_signals_are_scored_as_their_downmix() {
+        let x = noise(16_000, 1);
+        let y = noisy(&x, 1.0, 2);
+        let config = StoiConfig::default();
+        let expected = stoi_with_config(&x, &y, 16_000, &config);
+
+        // Identical channels downmix to the signal itself
+        let x_stereo = interleave(&[&x, &x]);
+        let y_stereo = interleave(&[&y, &y]);
+        assert_eq!(
+            stoi_interleaved(&x_stereo, &y_stereo, 16_000, 2, &config),
+            expected
+        );
+        assert!(stoi_interleaved(&x_stereo, &y_stereo, 16_000, 0, &config).is_err());
+        assert!(stoi_interleaved(&x_stereo[1..], &y_stereo[1..], 16_000, 2, &config).is_err());
+    }
+
+    #[test]
+    fn log_spectrograms_peak_on_the_sine_bin_above_the_floor() {
+        // A sine on bin 40, and a silent frame
+        let n = constants::FFT_LENGTH;
+        let frames = faer::Mat::from_fn(n, 2, |i, frame| match frame {
+            0 => (2.0 * std::f32::consts::PI * 40.0 * i as f32 / n as f32).sin(),
+            _ => 0.0,
+        });
+
+        let spectrogram = log_spectrogram(frames.as_ref(), -100.0).unwrap();
+        assert_eq!(spectrogram.nrows(), constants::FFT_BINS);
+        assert_eq!(spectrogram.ncols(), 2);
+
+        let sine = spectrogram.col(0);
+        let peak = (0..constants::FFT_BINS)
+            .max_by(|&a, &b| sine[a].total_cmp(&sine[b]))
+            .unwrap();
+        assert_eq!(peak, 40);
+        // |X| = n / 2 on the sine bin
+        assert!((sine[40] - 20.0 * (n as f32 / 2.0).log10()).abs() < 1e-3);
+        assert!(sine.iter().all(|&db| db >= -100.0));
+        assert!(spectrogram.col(1).iter().all(|&db| db == -100.0));
+
+        let too_long = faer::Mat::<f32>::zeros(n + 1, 1);
+        assert!(log_spectrogram(too_long.as_ref(), -100.0).is_err());
+    }
+
+    #[test]
+    fn level_normalization_brings_scaled_copies_together() {
+        // Speech with a quieter passage, which a fixed silence threshold
+        // only keeps at the louder level
+        let mut x = noise(48_000, 1);
+        x[16_000..32_000]
+            .iter_mut()
+            .for_each(|sample| *sample *= 0.03);
+        let y = noisy(&x, 0.3, 2);
+        let scale = |signal: &[f32]| {
+            signal
+                .iter()
+                .map(|sample| 0.05 * sample)
+                .collect::<Vec<_>>()
+        };
+        let (x_quiet, y_quiet) = (scale(&x), scale(&y));
+
+        let gap = |normalize_rms| {
+            let config = StoiConfig {
+                vad_reference_db: Some(0.0),
+                normalize_rms,
+                ..StoiConfig::default()
+            };
+            let loud = stoi_with_config(&x, &y, 16_000, &config).unwrap();
+            let quiet = stoi_with_config(&x_quiet, &y_quiet, 16_000, &config).unwrap();
+            (loud - quiet).abs()
+        };
+
+        let (raw, normalized) = (gap(false), gap(true));
+        assert!(raw > 1e-3, "{raw}");
+        assert!(
+            normalized < 1e-5 && normalized < raw,
+            "{normalized} vs {raw}"
+        );
+    }
+
+    #[test]
+    fn every_supported_rate_scores_in_strict_mode() {
+        let strict = StoiConfig {
+            strict_sample_rate: true,
+            ..StoiConfig::default()
+        };
+
+        for &fs in supported_sample_rates() {
+            let x = noise(2 * fs, 1);
+            let y = noisy(&x, 1.0, 2);
+            let score = stoi_with_config(&x, &y, fs, &strict).unwrap();
+            assert!(score > 0.0 && score < 1.0, "{fs}Hz: {score}");
+        }
+
+        // Other rates work, but only outside of strict mode
+        let x = noise(2 * 12_000, 3);
+        assert!(stoi(&x, &x, 12_000, false).is_ok());
+        assert_eq!(
+            stoi_with_config(&x, &x, 12_000, &strict),
+            Err(StoiError::InvalidSampleRate(12_000))
+        );
+    }
+
+    #[test]
+    fn inverted_signals_give_the_same_score() {
+        let x = noise(16_000, 1);
+        let y = noisy(&x, 1.0, 2);
+        let invert = |signal: &[f32]| signal.iter().map(|sample| -sample).collect::<Vec<_>>();
+
+        for extended in [false, true] {
+            let score = stoi(&x, &y, 16_000, extended).unwrap();
+            assert_eq!(stoi(&x, invert(&y), 16_000, extended).unwrap(), score);
+            assert_eq!(stoi(invert(&x), &y, 16_000, extended).unwrap(), score);
+        }
     }
 }
@@ -0,0 +1,85 @@
+//! Mel-spaced bands, as a research alternative to third octave bands
+
+use faer::prelude::*;
+
+use crate::{
+    constants::{FFT_BINS, FFT_LENGTH, FS},
+    octave,
+};
+
+/// Convert a frequency in Hz to the mel scale (HTK formula)
+fn hz_to_mel(freq: f32) -> f32 {
+    2595.0 * (1.0 + freq / 700.0).log10()
+}
+
+/// Convert a mel scale value to a frequency in Hz (HTK formula)
+fn mel_to_hz(mel: f32) -> f32 {
+    700.0 * (10.0_f32.powf(mel / 2595.0) - 1.0)
+}
+
+/// Triangular mel filterbank over the FFT bins, with shape (n_mels, FFT_BINS).
+///
+/// The filters are equally spaced on the mel scale, over the frequency range
+/// covered by the third octave bands. Each filter peaks at 1 on its center
+/// frequency and reaches 0 on the centers of its neighbours, so that the
+/// weights of all filters sum to 1 for every bin between the first and last
+/// centers. Bins outside of the range have zero weight.
+pub fn mel_filterbank(n_mels: usize) -> Mat<f32> {
+    assert!(n_mels > 0, "n_mels must be strictly positive");
+
+    let (min_freq, max_freq) = octave::frequency_range();
+    let (min_mel, max_mel) = (hz_to_mel(min_freq), hz_to_mel(max_freq));
+
+    // Filter edges and centers, with one more point on each side
+    let points: Vec<f32> = (0..n_mels + 2)
+        .map(|i| mel_to_hz(min_mel + (max_mel - min_mel) * i as f32 / (n_mels + 1) as f32))
+        .collect();
+
+    Mat::from_fn(n_mels, FFT_BINS, |band, bin| {
+        let freq = (bin * FS) as f32 / FFT_LENGTH as f32;
+        let (low, center, high) = (points[band], points[band + 1], points[band + 2]);
+
+        let rising = (freq - low) / (center - low);
+        let falling = (high - freq) / (high - center);
+        rising.min(falling).max(0.0)
+    })
+}
+
+/// Merge FFT spectrogram into mel bands with the weights of `filterbank`.
+/// Input spectrograms have shape (FFT_BINS, num_frames).
+/// The merged output has shape (n_mels, num_frames).
+pub fn compute_mel_bands(spectrogram: MatRef<f32>, filterbank: MatRef<f32>) -> Mat<f32> {
+    // The spectrogram contains squared magnitudes, as for octave bands
+    octave::compute_filterbank_bands(spectrogram, filterbank)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filterbank_weights_sum_to_one_between_the_first_and_last_centers() {
+        let n_mels = 20;
+        let filterbank = mel_filterbank(n_mels);
+        assert_eq!((filterbank.nrows(), filterbank.ncols()), (n_mels, FFT_BINS));
+
+        let (min_freq, max_freq) = octave::frequency_range();
+        let (min_mel, max_mel) = (hz_to_mel(min_freq), hz_to_mel(max_freq));
+        let center = |band: usize| {
+            mel_to_hz(min_mel + (max_mel - min_mel) * (band + 1) as f32 / (n_mels + 1) as f32)
+        };
+
+        for bin in 0..FFT_BINS {
+            let freq = (bin * FS) as f32 / FFT_LENGTH as f32;
+            let weights = filterbank.col(bin);
+            assert!(weights.iter().all(|w| (0.0..=1.0).contains(w)), "bin {bin}");
+
+            let sum: f32 = weights.iter().sum();
+            if (center(0)..=center(n_mels - 1)).contains(&freq) {
+                assert!((sum - 1.0).abs() < 1e-5, "bin {bin}: {sum}");
+            } else if !(min_freq..=max_freq).contains(&freq) {
+                assert_eq!(sum, 0.0, "bin {bin}");
+            }
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Export of intermediate arrays to `.npy` files
+
+use std::{fs::File, io::BufWriter, path::Path};
+
+use faer::MatRef;
+use npyz::{Order, WriterBuilder};
+
+use crate::{
+    config::StoiConfig,
+    constants::{FRAME_LENGTH, FS},
+    errors::{Result, StoiError},
+    frames, octave, pipeline, stft,
+};
+
+/// Compute the STOI measure with the reference parameters, writing the
+/// intermediate arrays of both signals to `.npy` files in `out_dir`.
+///
+/// This is a debugging tool to cross-check the pipeline against the python
+/// reference: arrays can be loaded with `np.load` and compared with the
+/// internals of `pystoi.stoi`. All arrays are `float32`, and `{x,y}` stands
+/// for the clean and processed signals:
+/// * `{x,y}_resampled.npy` - Signals at 10kHz, shape (len,).
+///   The reference `x` and `y` after `resample_oct`.
+/// * `{x,y}_frames.npy` - Windowed valid frames, shape (frames, 256):
+///   the frames of the reference `x_sil` and `y_sil` multiplied by the hann
+///   window, as they enter the reference `stft`.
+/// * `{x,y}_spectrogram.npy` - Squared magnitude spectrograms, shape (257, frames).
+///   The reference `np.abs(x_spec) ** 2`.
+/// * `{x,y}_bands.npy` - One-third octave band energies, shape (15, frames).
+///   The reference `x_tob` and `y_tob`.
+///
+/// Files are written before the score is computed, so that signals failing
+/// with [`StoiError::NotEnoughFrames`] can still be inspected. Existing files
+/// are overwritten, and write failures return [`StoiError::Npy`].
+///
+/// Args:
+/// * `x` - Clean speech signal
+/// * `y` - Processed speech signal
+/// * `fs_sig` - Sampling frequency of the signals
+/// * `extended` - Whether to use the extended STOI measure
+/// * `out_dir` - Existing directory to write the arrays to
+pub fn stoi_dump(
+    x: &[f32],
+    y: &[f32],
+    fs_sig: usize,
+    extended: bool,
+    out_dir: impl AsRef<Path>,
+) -> Result<f32> {
+    let out_dir = out_dir.as_ref();
+    let config = if extended {
+        StoiConfig::reference_extended()
+    } else {
+        StoiConfig::reference()
+    };
+    pipeline::check_inputs(x, y, fs_sig, &config)?;
+
+    let x = pipeline::to_internal_rate(x, fs_sig, &config);
+    let y = pipeline::to_internal_rate(y, fs_sig, &config);
+    write_npy(out_dir, "x_resampled", &[x.len()], Order::C, &x)?;
+    write_npy(out_dir, "y_resampled", &[y.len()], Order::C, &y)?;
+
+    // At least one frame is needed to compute frame energies
+    if x.len() <= FRAME_LENGTH {
+        return Err(pipeline::too_short(x.len(), FS, &config));
+    }
+
+    let frames = frames::process_frames(
+        &x[..],
+        &y[..],
+        &config,
+        frames::frame_windows(config.frame_window),
+    )?;
+    // Frames are stored contiguously, one frame after the other
+    let mut x_frames = vec![0.0; FRAME_LENGTH * frames.count];
+    let mut y_frames = vec![0.0; FRAME_LENGTH * frames.count];
+    for (k, (x_frame, y_frame)) in x_frames
+        .chunks_exact_mut(FRAME_LENGTH)
+        .zip(y_frames.chunks_exact_mut(FRAME_LENGTH))
+        .enumerate()
+    {
+        frames.read_x(k, x_frame);
+        frames.read_y(k, y_frame);
+    }
+
+    for (name, signal_frames) in [("x", &x_frames), ("y", &y_frames)] {
+        let shape = [frames.count, FRAME_LENGTH];
+        write_npy(
+            out_dir,
+            &format!("{name}_frames"),
+            &shape,
+            Order::C,
+            signal_frames,
+        )?;
+
+        let spectrogram = stft::compute_frame_rffts(frames.count, FRAME_LENGTH, |k, out| {
+            out.copy_from_slice(&signal_frames[k * FRAME_LENGTH..(k + 1) * FRAME_LENGTH])
+        });
+        write_mat(
+            out_dir,
+            &format!("{name}_spectrogram"),
+            spectrogram.as_ref(),
+        )?;
+
+        let bands = octave::compute_octave_bands(spectrogram.as_ref());
+        write_mat(out_dir, &format!("{name}_bands"), bands.as_ref())?;
+    }
+
+    let x_frames = MatRef::from_column_major_slice(&x_frames, FRAME_LENGTH, frames.count);
+    let y_frames = MatRef::from_column_major_slice(&y_frames, FRAME_LENGTH, frames.count);
+    crate::stoi_from_frames(x_frames, y_frames, extended)
+}
+
+/// Write a matrix to `{name}.npy` with the same shape, in Fortran order
+fn write_mat(out_dir: &Path, name: &str, mat: MatRef<f32>) -> Result<()> {
+    let data: Vec<f32> = mat.col_iter().flat_map(|col| col.iter().copied()).collect();
+
+    write_npy(
+        out_dir,
+        name,
+        &[mat.nrows(), mat.ncols()],
+        Order::Fortran,
+        &data,
+    )
+}
+
+/// Write data laid out in `order` to `{name}.npy` with the given shape
+fn write_npy(
+    out_dir: &Path,
+    name: &str,
+    shape: &[usize],
+    order: Order,
+    data: &[f32],
+) -> Result<()> {
+    let path = out_dir.join(format!("{name}.npy"));
+    let shape: Vec<u64> = shape.iter().map(|&dim| dim as u64).collect();
+
+    let file = File::create(path).map_err(npy_error)?;
+    let mut writer = npyz::WriteOptions::new()
+        .default_dtype()
+        .shape(&shape)
+        .order(order)
+        .writer(BufWriter::new(file))
+        .begin_nd()
+        .map_err(npy_error)?;
+    writer.extend(data.iter().copied()).map_err(npy_error)?;
+    writer.finish().map_err(npy_error)
+}
+
+fn npy_error(err: std::io::Error) -> StoiError {
+    StoiError::Npy(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{noise, noisy};
+
+    #[test]
+    fn dumped_arrays_reload_with_their_shapes() {
+        let out_dir = std::env::temp_dir().join("fast-stoi-npy-dump");
+        std::fs::create_dir_all(&out_dir).unwrap();
+        let x = noise(16_000, 1);
+        let y = noisy(&x, 1.0, 2);
+
+        let score = stoi_dump(&x, &y, 16_000, false, &out_dir).unwrap();
+        assert_eq!(Ok(score), crate::stoi(&x, &y, 16_000, false));
+
+        let load = |name: &str| {
+            let file = File::open(out_dir.join(format!("{name}.npy"))).unwrap();
+            let npy = npyz::NpyFile::new(file).unwrap();
+            let shape: Vec<usize> = npy.shape().iter().map(|&dim| dim as usize).collect();
+            let data = npy.into_vec::<f32>().unwrap();
+            (shape, data)
+        };
+
+        let (shape, data) = load("x_resampled");
+        assert_eq!(shape, [10_000]);
+        assert_eq!(data, crate::resample(&x, 16_000, FS));
+
+        let (frames_shape, _) = load("y_frames");
+        let count = frames_shape[0];
+        assert_eq!(frames_shape, [count, FRAME_LENGTH]);
+        for name in ["x", "y"] {
+            assert_eq!(load(&format!("{name}_spectrogram")).0, [257, count]);
+            assert_eq!(load(&format!("{name}_bands")).0, [15, count]);
+        }
+        std::fs::remove_dir_all(&out_dir).unwrap();
+    }
+}
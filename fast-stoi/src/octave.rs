@@ -1,11 +1,26 @@
 //! Third octave bands
+//!
+//! Band energies satisfy the following invariants, which refactors of this
+//! stage must preserve:
+//! * They are non-negative, as square roots of sums of squared magnitudes.
+//! * They are homogeneous: scaling a frame by `k` scales its FFT by `k`,
+//!   its squared magnitudes by `k²`, and thus all its band energies by `|k|`.
 
+use std::sync::Arc;
+
+use dashmap::DashMap;
 use faer::prelude::*;
+use lazy_static::lazy_static;
 
-use crate::constants::NUM_BANDS;
+use crate::{
+    constants::{FFT_BINS, FFT_LENGTH, FS, NUM_BANDS},
+    errors::{Result, StoiError},
+};
 
 /// Octave band indices in FFT spectrums of length 512
-/// (precomputed from the original STOI implementation)
+/// (precomputed from the original STOI implementation).
+/// They must match the bands of `octave_filterbank(FS, FFT_LENGTH)`, which
+/// scores use, as checked by the tests.
 const OCTAVE_BANDS: [(usize, usize); NUM_BANDS] = [
     (7, 9),
     (9, 11),
@@ -24,27 +39,332 @@ const OCTAVE_BANDS: [(usize, usize); NUM_BANDS] = [
     (174, 219),
 ];
 
-/// Merge FFT spectrogram into octave bands specified by the index ranges in `OCTAVE_BANDS`.
+lazy_static! {
+    /// Cache filterbanks for different (fs, fft_length)
+    static ref FILTERBANKS: DashMap<(usize, usize), Arc<Mat<f32>>> = DashMap::new();
+}
+
+/// Center frequency of the lowest one-third octave band
+const MIN_FREQ: f32 = 150.0;
+
+/// Center frequencies in Hz of the one-third octave bands.
+pub fn band_center_freqs() -> [f32; NUM_BANDS] {
+    std::array::from_fn(|k| MIN_FREQ * 2.0_f32.powf(k as f32 / 3.0))
+}
+
+/// Frequency range in Hz covered by the one-third octave bands,
+/// from the lowest edge of the first band to the highest edge of the last band.
+pub(crate) fn frequency_range() -> (f32, f32) {
+    let bin_freq = |bin: usize| (bin * FS) as f32 / FFT_LENGTH as f32;
+    (
+        bin_freq(OCTAVE_BANDS[0].0),
+        bin_freq(OCTAVE_BANDS[NUM_BANDS - 1].1),
+    )
+}
+
+/// Compute the energy of the `band`-th one-third octave band from the
+/// squared magnitude spectrum `rfft` of a frame (of length `FFT_BINS`).
+///
+/// This is a single band of `compute_octave_bands`, for when all bands are not needed.
+pub fn octave_band_energy(rfft: &[f32], band: usize) -> f32 {
+    assert!(band < NUM_BANDS, "band must be lower than {}", NUM_BANDS);
+    let (start, end) = OCTAVE_BANDS[band];
+    assert!(rfft.len() >= end, "Spectrum is too short for band {}", band);
+
+    band_energy(ColRef::from_slice(&rfft[start..end]))
+}
+
+/// Energy of a band from its squared magnitudes
+fn band_energy(band: ColRef<f32>) -> f32 {
+    // The spectrogram contains squared magnitudes,
+    // so we just need to sum and sqrt instead of norm_l2
+    debug_assert!(
+        band.iter()
+            .all(|&magnitude| magnitude >= 0.0 || magnitude.is_nan()),
+        "Squared magnitudes must be non-negative"
+    );
+    band.sum().sqrt()
+}
+
+/// Merge a squared magnitude spectrogram into one-third octave band energies.
+///
+/// The spectrogram has shape (bins, frames), with at least the 257 bins of
+/// a 512 samples FFT at 10kHz. Returns [`StoiError::SpectrumTooShort`] otherwise.
+/// The band energies have shape (15, frames).
+pub fn octave_bands(spectrogram: MatRef<f32>) -> Result<Mat<f32>> {
+    if spectrogram.nrows() < FFT_BINS {
+        return Err(StoiError::SpectrumTooShort {
+            bins: spectrogram.nrows(),
+            min_bins: FFT_BINS,
+        });
+    }
+
+    Ok(compute_octave_bands(spectrogram.subrows(0, FFT_BINS)))
+}
+
+/// One-third octave filterbank over the bins of an FFT of `fft_length` samples
+/// at `fs`, with shape (15, fft_length / 2 + 1).
+///
+/// Each band sums the bins from the one nearest to its lower edge (included)
+/// to the one nearest to its upper edge (excluded), with weights of 1, as the
+/// original STOI implementation. Bands may be empty at low resolutions.
+/// Filterbanks are cached for every (fs, fft_length) and never evicted.
+pub fn octave_filterbank(fs: usize, fft_length: usize) -> Arc<Mat<f32>> {
+    assert!(fs > 0, "fs must be strictly positive");
+    assert!(
+        fft_length > 0 && fft_length.is_multiple_of(2),
+        "fft_length must be even and strictly positive"
+    );
+
+    FILTERBANKS
+        .entry((fs, fft_length))
+        .or_insert_with(|| Arc::new(build_octave_filterbank(fs, fft_length)))
+        .clone()
+}
+
+/// Build the one-third octave filterbank, see [`octave_filterbank`]
+fn build_octave_filterbank(fs: usize, fft_length: usize) -> Mat<f32> {
+    let bins = fft_length / 2 + 1;
+    let bin_freq = |bin: usize| (bin * fs) as f64 / fft_length as f64;
+
+    // First bin with the smallest distance to a frequency
+    let nearest_bin = |freq: f64| {
+        (0..bins)
+            .min_by(|&a, &b| {
+                (bin_freq(a) - freq)
+                    .abs()
+                    .total_cmp(&(bin_freq(b) - freq).abs())
+            })
+            .unwrap()
+    };
+
+    let edges: Vec<(usize, usize)> = (0..NUM_BANDS)
+        .map(|k| {
+            let low = MIN_FREQ as f64 * 2.0_f64.powf((2 * k) as f64 / 6.0 - 1.0 / 6.0);
+            let high = MIN_FREQ as f64 * 2.0_f64.powf((2 * k) as f64 / 6.0 + 1.0 / 6.0);
+            (nearest_bin(low), nearest_bin(high))
+        })
+        .collect();
+
+    Mat::from_fn(NUM_BANDS, bins, |band, bin| {
+        let (start, end) = edges[band];
+        if (start..end).contains(&bin) {
+            1.0
+        } else {
+            0.0
+        }
+    })
+}
+
+/// Merge FFT spectrogram into the one-third octave bands of the original STOI implementation.
+/// Input shapes are not checked, see [`octave_bands`] for a checked version.
 /// Input spectrograms have shape (FFT_BINS, num_frames).
 /// The merged output has shape (NUM_BANDS, num_frames).
 pub fn compute_octave_bands(spectrogram: MatRef<f32>) -> Mat<f32> {
-    let num_frames = spectrogram.ncols();
-    let mut band_spectrogram = Mat::<f32>::zeros(NUM_BANDS, num_frames);
-
-    // Iterate over each frame
-    spectrogram
-        .col_iter()
-        .zip(band_spectrogram.col_iter_mut())
-        .for_each(|(rfft, bands)| {
-            bands
-                .iter_mut()
-                .zip(OCTAVE_BANDS.iter())
-                .for_each(|(band, &(start, end))| {
-                    // The spectrogram contains squared magnitudes,
-                    // so we just need to sum and sqrt instead of norm_l2
-                    *band = rfft.subrows(start, end - start).sum().sqrt();
-                });
-        });
+    compute_filterbank_bands(
+        spectrogram,
+        octave_filterbank(FS, FFT_LENGTH).as_ref().as_ref(),
+    )
+}
+
+/// Merge FFT spectrogram into bands with the weights of `filterbank`.
+/// Input spectrograms have shape (bins, num_frames), and filterbanks (num_bands, bins).
+/// The merged output has shape (num_bands, num_frames).
+///
+/// Performance notes:
+/// The bands are a single matrix multiplication, parallelized by faer with
+/// the `rayon` feature. Bins outside of all bands are skipped, which avoids
+/// reading most of the spectrogram for one-third octave bands (212 of 257 bins).
+/// This is as fast as summing the bins of every band in a loop (the former
+/// implementation), as both are bound by reading the spectrogram, but supports
+/// any band scheme (measured on 47000 frames and on full 10 minutes signals).
+pub fn compute_filterbank_bands(spectrogram: MatRef<f32>, filterbank: MatRef<f32>) -> Mat<f32> {
+    // Range of the bins with a weight in any band
+    let used = |bin: &usize| filterbank.col(*bin).iter().any(|&weight| weight != 0.0);
+    let start = (0..filterbank.ncols()).find(used).unwrap_or(0);
+    let end = (0..filterbank.ncols())
+        .rfind(used)
+        .map_or(start, |bin| bin + 1);
+
+    // The spectrogram contains squared magnitudes,
+    // so we just need to sum and sqrt instead of norm_l2
+    let mut band_spectrogram =
+        filterbank.subcols(start, end - start) * spectrogram.subrows(start, end - start);
+
+    band_spectrogram
+        .col_iter_mut()
+        .for_each(|bands| bands.iter_mut().for_each(|band| *band = band.sqrt()));
 
     band_spectrogram
 }
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// Squared magnitude spectrograms of 1 to 4 frames, with shape (FFT_BINS, frames)
+    fn spectrograms() -> impl Strategy<Value = Mat<f32>> {
+        (1..=4usize).prop_flat_map(|frames| {
+            prop::collection::vec(0.0f32..1e3, FFT_BINS * frames).prop_map(move |values| {
+                Mat::from_fn(FFT_BINS, frames, |bin, frame| {
+                    values[frame * FFT_BINS + bin]
+                })
+            })
+        })
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() <= 1e-5 * expected.abs().max(1.0),
+            "{actual} vs {expected}"
+        );
+    }
+
+    #[test]
+    fn bands_are_contiguous_increasing_and_inside_the_spectrum() {
+        for &(start, end) in &OCTAVE_BANDS {
+            assert!(start < end, "empty band {start}..{end}");
+            assert!(
+                end <= FFT_BINS,
+                "band {start}..{end} outside of the spectrum"
+            );
+        }
+        for pair in OCTAVE_BANDS.windows(2) {
+            assert_eq!(pair[0].1, pair[1].0, "gap between {pair:?}");
+        }
+    }
+
+    #[test]
+    fn single_band_energies_match_all_bands() {
+        let spectrum: Vec<f32> = (0..FFT_BINS)
+            .map(|bin| (bin as f32 * 0.37).sin().powi(2))
+            .collect();
+        let bands = compute_octave_bands(MatRef::from_column_major_slice(&spectrum, FFT_BINS, 1));
+
+        for band in 0..NUM_BANDS {
+            // The matrix product sums the bins in a different order
+            assert_close(octave_band_energy(&spectrum, band), bands[(band, 0)]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Spectrum is too short")]
+    fn single_band_energies_need_the_band_bins() {
+        octave_band_energy(&[1.0; 200], NUM_BANDS - 1);
+    }
+
+    #[test]
+    fn filterbanks_merge_any_amount_of_bands() {
+        // 4 rectangular bands of 2 bins, over 3 frames
+        let filterbank = Mat::from_fn(4, 8, |band, bin| (bin / 2 == band) as u8 as f32);
+        let spectrogram = Mat::from_fn(8, 3, |bin, frame| (bin + frame) as f32);
+
+        let bands = compute_filterbank_bands(spectrogram.as_ref(), filterbank.as_ref());
+        assert_eq!((bands.nrows(), bands.ncols()), (4, 3));
+        for band in 0..4 {
+            for frame in 0..3 {
+                let expected = ((4 * band + 2 * frame + 1) as f32).sqrt();
+                assert_eq!(bands[(band, frame)], expected, "band {band}, frame {frame}");
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn band_energies_are_the_sum_of_their_bins(spectrogram in spectrograms()) {
+            let bands = compute_octave_bands(spectrogram.as_ref());
+
+            for (frame, spectrum) in spectrogram.col_iter().enumerate() {
+                let spectrum: Vec<f32> = spectrum.iter().copied().collect();
+                for (band, &(start, end)) in OCTAVE_BANDS.iter().enumerate() {
+                    let expected = spectrum[start..end].iter().map(|&v| v as f64).sum::<f64>();
+                    let expected = expected.sqrt() as f32;
+                    assert_close(bands[(band, frame)], expected);
+                    assert_close(octave_band_energy(&spectrum, band), expected);
+                }
+            }
+        }
+
+        #[test]
+        fn band_energies_are_non_negative(spectrogram in spectrograms()) {
+            let bands = compute_octave_bands(spectrogram.as_ref());
+            prop_assert!(bands.col_iter().flat_map(|frame| frame.iter()).all(|&v| v >= 0.0));
+        }
+
+        #[test]
+        fn band_energies_are_homogeneous(spectrogram in spectrograms(), k in -10.0f32..10.0) {
+            // Scaling a frame by `k` scales its squared magnitudes by `k²`
+            let scaled = Mat::from_fn(spectrogram.nrows(), spectrogram.ncols(), |bin, frame| {
+                k * k * spectrogram[(bin, frame)]
+            });
+            let bands = compute_octave_bands(spectrogram.as_ref());
+            let scaled = compute_octave_bands(scaled.as_ref());
+
+            for frame in 0..bands.ncols() {
+                for band in 0..NUM_BANDS {
+                    assert_close(scaled[(band, frame)], k.abs() * bands[(band, frame)]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn filterbank_matches_the_band_table() {
+        let filterbank = octave_filterbank(FS, FFT_LENGTH);
+        assert_eq!(filterbank.nrows(), NUM_BANDS);
+        assert_eq!(filterbank.ncols(), FFT_BINS);
+
+        for (band, &(start, end)) in OCTAVE_BANDS.iter().enumerate() {
+            for bin in 0..FFT_BINS {
+                let expected = if (start..end).contains(&bin) {
+                    1.0
+                } else {
+                    0.0
+                };
+                assert_eq!(filterbank[(band, bin)], expected, "band {band}, bin {bin}");
+            }
+        }
+    }
+
+    #[test]
+    fn checked_band_energies_validate_the_spectrum_length() {
+        let narrow = Mat::<f32>::ones(FFT_BINS - 1, 3);
+        assert_eq!(
+            octave_bands(narrow.as_ref()),
+            Err(StoiError::SpectrumTooShort {
+                bins: FFT_BINS - 1,
+                min_bins: FFT_BINS,
+            })
+        );
+
+        // Bins above the spectrum of the reference FFT are ignored
+        let spectrogram = Mat::from_fn(FFT_BINS, 3, |bin, frame| (bin * (frame + 1)) as f32);
+        let wide = Mat::from_fn(FFT_BINS + 10, 3, |bin, frame| (bin * (frame + 1)) as f32);
+        let expected = compute_octave_bands(spectrogram.as_ref());
+        assert_eq!(octave_bands(spectrogram.as_ref()).unwrap(), expected);
+        assert_eq!(octave_bands(wide.as_ref()).unwrap(), expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "fft_length must be even")]
+    fn filterbanks_reject_odd_fft_lengths() {
+        octave_filterbank(FS, FFT_LENGTH - 1);
+    }
+
+    #[test]
+    fn filterbanks_at_other_rates_do_not_reuse_the_internal_rate() {
+        let internal = octave_filterbank(FS, FFT_LENGTH);
+        let other = octave_filterbank(16_000, FFT_LENGTH);
+        assert!(!Arc::ptr_eq(&internal, &other));
+        assert!(Arc::ptr_eq(&other, &octave_filterbank(16_000, FFT_LENGTH)));
+
+        // The lowest band starts near 134Hz: bin 7 of 19.5Hz, or bin 4 of 31.25Hz
+        let first_bin =
+            |filterbank: &Mat<f32>| (0..FFT_BINS).find(|&bin| filterbank[(0, bin)] > 0.0);
+        assert_eq!(first_bin(&internal), Some(OCTAVE_BANDS[0].0));
+        assert_eq!(first_bin(&internal), Some(7));
+        assert_eq!(first_bin(&other), Some(4));
+    }
+}
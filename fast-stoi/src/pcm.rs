@@ -0,0 +1,184 @@
+//! Audio input conversions
+
+/// Convert 16-bit PCM samples to floats in `[-1, 1)`.
+///
+/// Samples are divided by 32768 (2^15), so that `i16::MIN` maps exactly to -1
+/// and `i16::MAX` to 1 - 2^-15. This is the usual convention for decoding audio,
+/// also used by `soundfile` and `librosa`.
+pub fn normalize_pcm_i16(samples: &[i16]) -> Vec<f32> {
+    samples
+        .iter()
+        .map(|&sample| sample as f32 / 32768.0)
+        .collect()
+}
+
+/// Convert 24-bit PCM samples stored in `i32` to floats in `[-1, 1)`.
+///
+/// Samples are sign-extended from their low 24 bits, and their upper 8 bits
+/// are ignored: both sign-extended values in `[-2^23, 2^23)` and unsigned
+/// 24-bit words (`0x800000` for -2^23) are read as 24-bit samples, and values
+/// outside of the 24-bit range wrap around instead of exceeding full scale.
+/// They are divided by 8388608 (2^23), so that -2^23 maps exactly to -1
+/// and 2^23 - 1 to 1 - 2^-23.
+pub fn normalize_pcm_i24(samples: &[i32]) -> Vec<f32> {
+    samples
+        .iter()
+        .map(|&sample| ((sample << 8) >> 8) as f32 / 8388608.0)
+        .collect()
+}
+
+/// Downmix multichannel audio to mono.
+///
+/// Channels are averaged with equal weights by default, or summed with the
+/// given `weights`, one per channel. All channels must have the same length.
+pub fn downmix(channels: &[&[f32]], weights: Option<&[f32]>) -> Vec<f32> {
+    assert!(!channels.is_empty(), "At least one channel is required");
+    let len = channels[0].len();
+    assert!(
+        channels.iter().all(|channel| channel.len() == len),
+        "Channels must have the same length"
+    );
+    if let Some(weights) = weights {
+        assert!(
+            weights.len() == channels.len(),
+            "There must be one weight per channel"
+        );
+    }
+
+    let equal_weight = 1.0 / channels.len() as f32;
+    let mut mono = vec![0.0; len];
+
+    channels.iter().enumerate().for_each(|(i, channel)| {
+        let weight = weights.map_or(equal_weight, |weights| weights[i]);
+        mono.iter_mut()
+            .zip(channel.iter())
+            .for_each(|(mono, sample)| *mono += weight * sample);
+    });
+
+    mono
+}
+
+/// Split interleaved multichannel audio (`[l0, r0, l1, r1, ...]`) into channels.
+///
+/// The length of `data` must be a multiple of `channels`.
+pub fn deinterleave(data: &[f32], channels: usize) -> Vec<Vec<f32>> {
+    assert!(channels > 0, "At least one channel is required");
+    assert!(
+        data.len().is_multiple_of(channels),
+        "Interleaved length must be a multiple of the amount of channels"
+    );
+
+    (0..channels)
+        .map(|c| data.iter().skip(c).step_by(channels).copied().collect())
+        .collect()
+}
+
+/// Interleave channels into multichannel audio (`[l0, r0, l1, r1, ...]`),
+/// the inverse of [`deinterleave`]. All channels must have the same length.
+pub fn interleave(channels: &[&[f32]]) -> Vec<f32> {
+    assert!(!channels.is_empty(), "At least one channel is required");
+    let len = channels[0].len();
+    assert!(
+        channels.iter().all(|channel| channel.len() == len),
+        "Channels must have the same length"
+    );
+
+    (0..len)
+        .flat_map(|i| channels.iter().map(move |channel| channel[i]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn i16_full_scale_maps_to_unit_range() {
+        let samples = normalize_pcm_i16(&[i16::MIN, -1, 0, 1, i16::MAX]);
+        assert_eq!(
+            samples,
+            [
+                -1.0,
+                -1.0 / 32768.0,
+                0.0,
+                1.0 / 32768.0,
+                1.0 - 1.0 / 32768.0
+            ]
+        );
+    }
+
+    #[test]
+    fn i24_full_scale_maps_to_unit_range() {
+        let min = -(1 << 23);
+        let max = (1 << 23) - 1;
+        let samples = normalize_pcm_i24(&[min, -1, 0, max]);
+        assert_eq!(
+            samples,
+            [-1.0, -1.0 / 8388608.0, 0.0, 1.0 - 1.0 / 8388608.0]
+        );
+    }
+
+    #[test]
+    fn i24_samples_are_sign_extended_from_24_bits() {
+        // Unsigned 24-bit words
+        assert_eq!(
+            normalize_pcm_i24(&[0x80_0000, 0xFF_FFFF]),
+            [-1.0, -1.0 / 8388608.0]
+        );
+        // Upper bits are ignored, so that no sample exceeds full scale
+        assert_eq!(
+            normalize_pcm_i24(&[1 << 30, i32::MAX, i32::MIN]),
+            [0.0, -1.0 / 8388608.0, 0.0]
+        );
+        assert!(
+            normalize_pcm_i24(&[i32::MIN, i32::MAX, 1 << 30, 0x12_3456])
+                .iter()
+                .all(|sample| (-1.0..1.0).contains(sample))
+        );
+    }
+
+    #[test]
+    fn channels_are_downmixed_with_their_weights() {
+        let left = [1.0, 0.5, -1.0];
+        let right = [0.0, 0.5, 1.0];
+
+        assert_eq!(downmix(&[&left, &right], None), vec![0.5, 0.5, 0.0]);
+        assert_eq!(
+            downmix(&[&left, &right], Some(&[0.75, 0.25])),
+            vec![0.75, 0.5, -0.5]
+        );
+        // The weights are not normalized
+        assert_eq!(
+            downmix(&[&left, &right], Some(&[2.0, 2.0])),
+            vec![2.0, 2.0, 0.0]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "one weight per channel")]
+    fn downmixing_needs_one_weight_per_channel() {
+        downmix(&[&[1.0], &[1.0]], Some(&[1.0]));
+    }
+
+    #[test]
+    fn interleaving_round_trips() {
+        let data = [0.0, 1.0, 2.0, 3.0, 4.0, 5.0];
+        let stereo = deinterleave(&data, 2);
+        assert_eq!(stereo, vec![vec![0.0, 2.0, 4.0], vec![1.0, 3.0, 5.0]]);
+        let channels: Vec<&[f32]> = stereo.iter().map(Vec::as_slice).collect();
+        assert_eq!(interleave(&channels), data);
+
+        let three = deinterleave(&data, 3);
+        assert_eq!(three, vec![vec![0.0, 3.0], vec![1.0, 4.0], vec![2.0, 5.0]]);
+        let channels: Vec<&[f32]> = three.iter().map(Vec::as_slice).collect();
+        assert_eq!(interleave(&channels), data);
+
+        assert_eq!(deinterleave(&data, 1), vec![data.to_vec()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "multiple of the amount of channels")]
+    fn deinterleaving_needs_whole_frames() {
+        deinterleave(&[0.0; 5], 2);
+    }
+}
@@ -0,0 +1,740 @@
+//! Full STOI pipeline from raw signals to segment correlations
+
+use std::borrow::Cow;
+
+use faer::prelude::*;
+
+use crate::{
+    config::{BandScheme, NanPolicy, StoiConfig},
+    constants::{CLIPPING_RATIO, FRAME_LENGTH, FS, MAX_BUFFER_LEN},
+    errors::{Result, StoiError},
+    estimate, extended, fft_resample,
+    frames::{self, FrameSource, FrameWindows, frame_windows},
+    mel, octave, resample,
+    resample::ResampleMethod,
+    standard, stft,
+};
+
+/// Segment band correlations along with frame statistics
+pub struct Correlations {
+    /// Correlations with shape (num_bands, num_segments).
+    /// Their mean is the STOI score.
+    pub values: Mat<f32>,
+    /// Amount of frames kept after removing silent frames
+    pub valid_frames: usize,
+    /// Amount of frames before removing silent frames
+    pub total_frames: usize,
+    /// How NaN correlations are averaged
+    pub nan_policy: NanPolicy,
+    /// Whether to clamp the score to `[-1, 1]`
+    pub clamp_score: bool,
+    /// Optional weights of the band scores in the score
+    pub band_weights: Option<Vec<f32>>,
+}
+
+impl Correlations {
+    /// Average the correlations into the STOI score
+    pub fn score(&self) -> f32 {
+        let score = match &self.band_weights {
+            Some(weights) => self.weighted_mean(&self.band_scores(), weights),
+            None => self.mean(self.values.col_iter().flat_map(|segment| segment.iter())),
+        };
+
+        if self.clamp_score {
+            score.clamp(-1.0, 1.0)
+        } else {
+            score
+        }
+    }
+
+    /// Average the correlations over segments for each band
+    pub fn band_scores(&self) -> Vec<f32> {
+        self.values
+            .row_iter()
+            .map(|band| self.mean(band.iter()))
+            .collect()
+    }
+
+    /// Average correlations according to the NaN policy
+    fn mean<'a>(&self, values: impl Iterator<Item = &'a f32>) -> f32 {
+        let mut sum = KahanSum::default();
+        let mut count = 0;
+        let mut skipped = 0;
+
+        for &value in values {
+            if value.is_nan() && self.nan_policy == NanPolicy::Skip {
+                skipped += 1;
+                continue;
+            }
+            sum.add(value);
+            count += 1;
+        }
+
+        if skipped > 0 {
+            log::warn!(
+                "Skipped {} NaN correlations out of {}",
+                skipped,
+                skipped + count
+            );
+        }
+
+        sum.value() / count as f32
+    }
+
+    /// Weighted average of band scores according to the NaN policy
+    fn weighted_mean(&self, band_scores: &[f32], weights: &[f32]) -> f32 {
+        let mut sum = KahanSum::default();
+        let mut total = KahanSum::default();
+        band_scores
+            .iter()
+            .zip(weights)
+            .filter(|(score, _)| !(score.is_nan() && self.nan_policy == NanPolicy::Skip))
+            .for_each(|(score, weight)| {
+                sum.add(score * weight);
+                total.add(*weight);
+            });
+
+        sum.value() / total.value()
+    }
+}
+
+/// Compensated (Kahan) summation, to average correlations of long signals.
+///
+/// A naive `f32` sum loses the low bits of every correlation once the sum
+/// is large: averaging the 2 million correlations of a 30 minutes signal
+/// drifts by 4.5e-3 from the exact mean, against less than 1e-7 with compensation.
+/// The summation order is fixed, so that scores do not depend on the platform.
+#[derive(Default)]
+struct KahanSum {
+    sum: f32,
+    /// Low-order bits lost by the previous additions
+    compensation: f32,
+}
+
+impl KahanSum {
+    fn add(&mut self, value: f32) {
+        let value = value - self.compensation;
+        let sum = self.sum + value;
+        self.compensation = (sum - self.sum) - value;
+        self.sum = sum;
+    }
+
+    fn value(&self) -> f32 {
+        self.sum
+    }
+}
+
+/// Resample a signal to the internal sampling frequency.
+/// Signals already at the internal sampling frequency are borrowed.
+pub fn to_internal_rate<'a>(x: &'a [f32], fs_sig: usize, config: &StoiConfig) -> Cow<'a, [f32]> {
+    if fs_sig != FS {
+        Cow::Owned(match config.resample_method {
+            ResampleMethod::Polyphase => resample::resample_padded(
+                x,
+                fs_sig,
+                FS,
+                config.resample_quality,
+                config.resample_padding,
+            ),
+            ResampleMethod::Fft => fft_resample::resample_fft(x, fs_sig, FS),
+        })
+    } else {
+        Cow::Borrowed(x)
+    }
+}
+
+/// Error for signals of length `len` sampled at `fs_sig` that are too short
+pub fn too_short(len: usize, fs_sig: usize, config: &StoiConfig) -> StoiError {
+    StoiError::SignalTooShort {
+        len,
+        fs: fs_sig,
+        min_len: estimate::min_signal_len(fs_sig, config),
+    }
+}
+
+/// Check that signals can be resampled from `fs_sig`: the sampling frequency
+/// is strictly positive, and small enough for its polyphase filter to be allocated
+pub fn check_sample_rate(fs_sig: usize, config: &StoiConfig) -> Result<()> {
+    if fs_sig == 0 {
+        return Err(StoiError::InvalidSampleRate(fs_sig));
+    }
+    if fs_sig != FS
+        && config.resample_method == ResampleMethod::Polyphase
+        && resample::filter_length(fs_sig, FS, config.resample_quality).is_none()
+    {
+        return Err(StoiError::InvalidSampleRate(fs_sig));
+    }
+    Ok(())
+}
+
+/// Check that scores at `fs_sig` are validated against the reference,
+/// warning or failing in strict mode otherwise
+pub fn check_validated_rate(fs_sig: usize, config: &StoiConfig) -> Result<()> {
+    if resample::supported_sample_rates().contains(&fs_sig) {
+        return Ok(());
+    }
+    if config.strict_sample_rate {
+        return Err(StoiError::InvalidSampleRate(fs_sig));
+    }
+
+    log::warn!(
+        "Scores at {}Hz are not validated against the reference",
+        fs_sig
+    );
+    Ok(())
+}
+
+/// Check that signals of length `len` sampled at `fs_sig` do not exceed
+/// the maximum length once resampled to 10kHz, nor the maximum buffer length
+pub fn check_length(len: usize, fs_sig: usize, config: &StoiConfig) -> Result<()> {
+    let max_len = config
+        .max_signal_len
+        .map_or(MAX_BUFFER_LEN, |max_len| max_len.min(MAX_BUFFER_LEN));
+
+    // Resampled lengths of huge signals may overflow
+    let len = usize::try_from(len as u128 * FS as u128 / fs_sig as u128).unwrap_or(usize::MAX);
+    if len > max_len {
+        return Err(StoiError::SignalTooLong { len, max_len });
+    }
+
+    Ok(())
+}
+
+/// Check the input signals and parameters before any processing
+pub fn check_inputs(x: &[f32], y: &[f32], fs_sig: usize, config: &StoiConfig) -> Result<()> {
+    check_sample_rate(fs_sig, config)?;
+    if x.is_empty() || y.is_empty() {
+        return Err(too_short(0, fs_sig, config));
+    }
+    if x.len() != y.len() {
+        return Err(StoiError::LengthMismatch {
+            x_len: x.len(),
+            y_len: y.len(),
+        });
+    }
+    config.validate()?;
+    check_validated_rate(fs_sig, config)?;
+    check_length(x.len(), fs_sig, config)?;
+
+    if config.strict_clipping {
+        check_clipping(x)?;
+        check_clipping(y)?;
+    }
+
+    Ok(())
+}
+
+/// Check that a signal does not have too many full scale samples
+pub fn check_clipping(x: &[f32]) -> Result<()> {
+    let count = x.iter().filter(|sample| sample.abs() >= 1.0).count();
+
+    if count as f32 > CLIPPING_RATIO * x.len() as f32 {
+        return Err(StoiError::Clipped { count });
+    }
+
+    Ok(())
+}
+
+/// Do the full computation from signals at any sampling frequency
+pub fn correlations(
+    x: &[f32],
+    y: &[f32],
+    fs_sig: usize,
+    config: &StoiConfig,
+) -> Result<Correlations> {
+    check_inputs(x, y, fs_sig, config)?;
+
+    // Identical signals are only resampled once
+    if config.identical_shortcut && x == y {
+        let x = to_internal_rate(x, fs_sig, config);
+        return compute(&x, &x, config);
+    }
+
+    let x = to_internal_rate(x, fs_sig, config);
+    let y = to_internal_rate(y, fs_sig, config);
+
+    compute(&x, &y, config)
+}
+
+/// Do the full computation post resampling to 10kHz
+pub fn compute(x: &[f32], y: &[f32], config: &StoiConfig) -> Result<Correlations> {
+    let windows = frame_windows(config.frame_window);
+    let [correlations] = compute_measures(x, y, config, [config.extended], windows)?;
+    Ok(correlations)
+}
+
+/// Do the full computation post resampling to 10kHz with custom frame windows
+pub fn compute_with_windows(
+    x: &[f32],
+    y: &[f32],
+    config: &StoiConfig,
+    windows: &FrameWindows,
+) -> Result<Correlations> {
+    let [correlations] = compute_measures(x, y, config, [config.extended], windows)?;
+    Ok(correlations)
+}
+
+/// Do the full computation post resampling to 10kHz
+/// for both the standard and extended measures, in this order
+pub fn compute_both(
+    x: &[f32],
+    y: &[f32],
+    config: &StoiConfig,
+) -> Result<(Correlations, Correlations)> {
+    let windows = frame_windows(config.frame_window);
+    let [standard, extended] = compute_measures(x, y, config, [false, true], windows)?;
+    Ok((standard, extended))
+}
+
+/// Compute the correlations of several measures (`true` for extended)
+/// sharing all stages before the correlation stage
+fn compute_measures<const N: usize>(
+    x: &[f32],
+    y: &[f32],
+    config: &StoiConfig,
+    measures: [bool; N],
+    windows: &FrameWindows,
+) -> Result<[Correlations; N]> {
+    let (x, y) = if config.trim_silence {
+        frames::trim_silence(x, y)
+    } else {
+        (x, y)
+    };
+
+    let identical = config.identical_shortcut && std::ptr::eq(x, y);
+    let (x, y) = if config.normalize_rms {
+        (frames::normalize_rms(x), frames::normalize_rms(y))
+    } else {
+        (Cow::Borrowed(x), Cow::Borrowed(y))
+    };
+    let x = frames::pad_partial(&x, config.hop_length, config.include_partial);
+    let y = frames::pad_partial(&y, config.hop_length, config.include_partial);
+    compute_measures_from_sources(&*x, &*y, config, measures, identical, windows)
+}
+
+/// Do the full computation from signal sources at 10kHz
+pub fn compute_from_sources<S: FrameSource + ?Sized>(
+    x: &S,
+    y: &S,
+    config: &StoiConfig,
+) -> Result<Correlations> {
+    let windows = frame_windows(config.frame_window);
+    let [correlations] =
+        compute_measures_from_sources(x, y, config, [config.extended], false, windows)?;
+    Ok(correlations)
+}
+
+/// Compute the correlations of several measures (`true` for extended)
+/// from signal sources, sharing all stages before the correlation stage.
+/// Identical signals skip the computation.
+fn compute_measures_from_sources<S: FrameSource + ?Sized, const N: usize>(
+    x: &S,
+    y: &S,
+    config: &StoiConfig,
+    measures: [bool; N],
+    identical: bool,
+    windows: &FrameWindows,
+) -> Result<[Correlations; N]> {
+    // At least one frame is needed to compute frame energies
+    if x.len() <= FRAME_LENGTH {
+        return Err(too_short(x.len(), FS, config));
+    }
+
+    // Compute frames
+    let frames = frames::process_frames(x, y, config, windows)?;
+
+    measures_from_frames(
+        frames.count,
+        frames.total,
+        |k, out| frames.read_x(k, out),
+        |k, out| frames.read_y(k, out),
+        config,
+        measures,
+        identical,
+    )
+}
+
+/// Do the computation from `count` windowed valid frames of both signals at 10kHz.
+/// `read_x(k, out)` and `read_y(k, out)` write the `k`-th frame of each signal into `out`.
+pub fn compute_from_frames(
+    count: usize,
+    read_x: impl FnMut(usize, &mut [f32]),
+    read_y: impl FnMut(usize, &mut [f32]),
+    config: &StoiConfig,
+) -> Result<Correlations> {
+    let [correlations] = measures_from_frames(
+        count,
+        count,
+        read_x,
+        read_y,
+        config,
+        [config.extended],
+        false,
+    )?;
+    Ok(correlations)
+}
+
+/// Compute the correlations of several measures (`true` for extended)
+/// from `count` valid frames among `total` frames, sharing all stages
+/// before the correlation stage. Identical signals skip the computation.
+fn measures_from_frames<const N: usize>(
+    count: usize,
+    total: usize,
+    read_x: impl FnMut(usize, &mut [f32]),
+    read_y: impl FnMut(usize, &mut [f32]),
+    config: &StoiConfig,
+    measures: [bool; N],
+    identical: bool,
+) -> Result<[Correlations; N]> {
+    let padded = config.pad_short && count > 0;
+    let min_frames = config.min_valid_frames();
+    if count < min_frames && !padded {
+        return Err(StoiError::NotEnoughFrames {
+            valid_frames: count,
+            segment_frames: min_frames,
+        });
+    }
+
+    let num_bands = config.band_scheme.num_bands();
+    let mut band_weights = None;
+    let values = if identical {
+        // Identical signals are perfectly correlated
+        let num_segments = count.max(min_frames) - config.segment_frames + 1;
+        measures.map(|_| Mat::full(num_bands, num_segments, 1.0))
+    } else {
+        let (x_segments, y_segments, weights) = band_segments(count, read_x, read_y, config);
+        band_weights = weights;
+        let mut segments = Some((x_segments, y_segments));
+
+        // Segments are normalized in place: only the last measure can consume them
+        std::array::from_fn(|i| {
+            let (mut x_segments, mut y_segments) = if i + 1 == N {
+                segments.take().unwrap()
+            } else {
+                segments.clone().unwrap()
+            };
+
+            if measures[i] {
+                extended::from_segments(x_segments.as_mut(), y_segments.as_mut(), num_bands)
+            } else {
+                standard::from_segments(
+                    x_segments.as_mut(),
+                    y_segments.as_mut(),
+                    num_bands,
+                    config.clip_db,
+                    config.correlation,
+                )
+            }
+        })
+    };
+
+    // Exclude the trimmed segments, at least one segment remaining
+    let trim = config.trim_segments;
+    let values = values.map(|values| {
+        if trim == 0 {
+            values
+        } else {
+            values.subcols(trim, values.ncols() - 2 * trim).to_owned()
+        }
+    });
+
+    Ok(values.map(|values| Correlations {
+        values,
+        valid_frames: count,
+        total_frames: total,
+        nan_policy: config.nan_policy,
+        clamp_score: config.clamp_score,
+        band_weights: band_weights.clone(),
+    }))
+}
+
+/// Band segments of both signals, along with the band weights if any
+type BandSegments = (Mat<f32>, Mat<f32>, Option<Vec<f32>>);
+
+/// Compute the band segments of both signals from their `count` valid frames.
+/// The segments have shapes (segment_length, num_segments * num_bands).
+/// With `config.weight_bands`, the band weights are the clean band energies.
+fn band_segments(
+    count: usize,
+    read_x: impl FnMut(usize, &mut [f32]),
+    read_y: impl FnMut(usize, &mut [f32]),
+    config: &StoiConfig,
+) -> BandSegments {
+    // Compute spectrograms
+    let x_spec = stft::compute_frame_rffts(count, FRAME_LENGTH, read_x);
+    let y_spec = stft::compute_frame_rffts(count, FRAME_LENGTH, read_y);
+
+    // Accumulate into frequency bands
+    let (x_bands, y_bands) = match config.band_scheme {
+        BandScheme::ThirdOctave => (
+            octave::compute_octave_bands(x_spec.as_ref()),
+            octave::compute_octave_bands(y_spec.as_ref()),
+        ),
+        BandScheme::Mel { n_mels } => {
+            let filterbank = mel::mel_filterbank(n_mels);
+            (
+                mel::compute_mel_bands(x_spec.as_ref(), filterbank.as_ref()),
+                mel::compute_mel_bands(y_spec.as_ref(), filterbank.as_ref()),
+            )
+        }
+    };
+
+    // Clean energies of the valid frames in every band
+    let weights = config.weight_bands.then(|| {
+        x_bands
+            .row_iter()
+            .map(|band| band.iter().map(|energy| energy * energy).sum())
+            .collect()
+    });
+
+    // Pad short signals up to a single segment, along with the trimmed segments
+    let min_frames = config.min_valid_frames();
+    let (x_bands, y_bands) = if count < min_frames {
+        (
+            frames::reflect_pad(x_bands.as_ref(), min_frames),
+            frames::reflect_pad(y_bands.as_ref(), min_frames),
+        )
+    } else {
+        (x_bands, y_bands)
+    };
+
+    // Slice into segments
+    let mut x_segments = frames::segments(x_bands.transpose(), config.segment_frames);
+    let mut y_segments = frames::segments(y_bands.transpose(), config.segment_frames);
+
+    if let Some(floor_db) = config.band_floor_db {
+        let num_bands = config.band_scheme.num_bands();
+        frames::floor_segments(x_segments.as_mut(), num_bands, floor_db);
+        frames::floor_segments(y_segments.as_mut(), num_bands, floor_db);
+    }
+
+    (x_segments, y_segments, weights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Correlations of 2 bands over 2 segments, with a NaN in the first band
+    fn correlations(nan_policy: NanPolicy) -> Correlations {
+        Correlations {
+            values: mat![[0.5, f32::NAN], [0.25, 1.0]],
+            valid_frames: 60,
+            total_frames: 60,
+            nan_policy,
+            clamp_score: false,
+            band_weights: None,
+        }
+    }
+
+    #[test]
+    fn skipped_nan_correlations_are_left_out_of_the_averages() {
+        let correlations = correlations(NanPolicy::Skip);
+        assert!((correlations.score() - 1.75 / 3.0).abs() < 1e-6);
+        assert_eq!(correlations.band_scores(), vec![0.5, 0.625]);
+
+        let weighted = Correlations {
+            band_weights: Some(vec![1.0, 3.0]),
+            ..correlations
+        };
+        assert_eq!(weighted.score(), (0.5 + 3.0 * 0.625) / 4.0);
+    }
+
+    #[test]
+    fn propagated_nan_correlations_turn_the_averages_into_nan() {
+        let correlations = correlations(NanPolicy::Propagate);
+        assert!(correlations.score().is_nan());
+
+        let band_scores = correlations.band_scores();
+        assert!(band_scores[0].is_nan());
+        assert_eq!(band_scores[1], 0.625);
+
+        let weighted = Correlations {
+            band_weights: Some(vec![1.0, 3.0]),
+            ..correlations
+        };
+        assert!(weighted.score().is_nan());
+    }
+
+    #[test]
+    fn scores_beyond_one_are_only_clamped_on_demand() {
+        // Rounding errors may push the correlations of identical signals above 1
+        let correlations = Correlations {
+            values: mat![
+                [1.0 + 4.0 * f32::EPSILON, 1.0],
+                [1.0 + 2.0 * f32::EPSILON, 1.0]
+            ],
+            ..correlations(NanPolicy::Skip)
+        };
+        assert!(correlations.score() > 1.0);
+
+        let clamped = Correlations {
+            clamp_score: true,
+            ..correlations
+        };
+        assert_eq!(clamped.score(), 1.0);
+    }
+
+    #[test]
+    fn clamped_scores_of_identical_signals_are_at_most_one() {
+        let x = crate::test_signals::noise(30_000, 1);
+        for extended in [false, true] {
+            let config = StoiConfig {
+                extended,
+                clamp_score: true,
+                ..StoiConfig::default()
+            };
+            let score = crate::stoi_with_config(&x, &x, 16_000, &config).unwrap();
+            assert!(score <= 1.0 && score > 1.0 - 1e-5, "{score}");
+        }
+    }
+
+    #[test]
+    fn only_identical_signals_take_the_shortcut() {
+        let x = crate::test_signals::noise(30_000, 2);
+        let near = crate::test_signals::noisy(&x, 0.01, 3);
+
+        for extended in [false, true] {
+            let config = StoiConfig {
+                extended,
+                identical_shortcut: true,
+                ..StoiConfig::default()
+            };
+            let full = StoiConfig {
+                identical_shortcut: false,
+                ..config.clone()
+            };
+
+            assert_eq!(
+                crate::stoi_with_config(&x, x.clone(), 16_000, &config),
+                Ok(1.0)
+            );
+            // Near-identical signals are scored as without the shortcut
+            let score = crate::stoi_with_config(&x, &near, 16_000, &config).unwrap();
+            assert_eq!(
+                score,
+                crate::stoi_with_config(&x, &near, 16_000, &full).unwrap()
+            );
+            assert!(score < 1.0 && score > 0.99, "{score}");
+        }
+
+        // Errors are those of the full computation
+        let config = StoiConfig {
+            identical_shortcut: true,
+            ..StoiConfig::default()
+        };
+        let silent = vec![0.0; 30_000];
+        assert_eq!(
+            crate::stoi_with_config(&silent, &silent, 16_000, &config),
+            Err(StoiError::SilentSignal)
+        );
+    }
+
+    #[test]
+    fn strict_mode_rejects_signals_with_injected_clipping() {
+        let x: Vec<f32> = crate::test_signals::noise(30_000, 4)
+            .iter()
+            .map(|sample| 0.5 * sample)
+            .collect();
+        let clip = |count: usize| {
+            let mut y = x.clone();
+            y.iter_mut()
+                .step_by(100)
+                .take(count)
+                .for_each(|sample| *sample = -1.0);
+            y
+        };
+        let strict = StoiConfig {
+            strict_clipping: true,
+            ..StoiConfig::default()
+        };
+
+        // Up to 0.1% of full scale samples are tolerated
+        assert!(crate::stoi_with_config(&x, clip(30), 16_000, &strict).is_ok());
+        assert_eq!(
+            crate::stoi_with_config(&x, clip(31), 16_000, &strict),
+            Err(StoiError::Clipped { count: 31 })
+        );
+        assert_eq!(
+            crate::stoi_with_config(clip(31), &x, 16_000, &strict),
+            Err(StoiError::Clipped { count: 31 })
+        );
+        assert!(crate::stoi_with_config(&x, clip(31), 16_000, &StoiConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn equal_band_weights_reproduce_the_unweighted_score() {
+        // Band scores of 0.625 and 0.375
+        let weighted = |band_weights| {
+            Correlations {
+                values: mat![[0.5, 0.75], [0.25, 0.5]],
+                band_weights,
+                ..correlations(NanPolicy::Skip)
+            }
+            .score()
+        };
+        assert_eq!(weighted(None), 0.5);
+        assert_eq!(weighted(Some(vec![2.0, 2.0])), 0.5);
+        assert_eq!(weighted(Some(vec![1.0, 3.0])), 0.4375);
+    }
+
+    #[test]
+    fn lengths_are_checked_at_the_limit_without_allocating() {
+        let config = StoiConfig::default();
+        let too_long = |len, max_len| Err(StoiError::SignalTooLong { len, max_len });
+        let max_len = crate::constants::MAX_SIGNAL_LEN;
+
+        assert_eq!(check_length(max_len, FS, &config), Ok(()));
+        assert_eq!(
+            check_length(max_len + 1, FS, &config),
+            too_long(max_len + 1, max_len)
+        );
+        // Lengths are compared once resampled to 10kHz
+        assert_eq!(check_length(max_len / 10 * 16, 16_000, &config), Ok(()));
+        assert_eq!(
+            check_length(max_len / 10 * 16 + 2, 16_000, &config),
+            too_long(max_len + 1, max_len)
+        );
+
+        // Resampled lengths saturate instead of overflowing
+        assert_eq!(
+            check_length(usize::MAX, 1, &config),
+            too_long(usize::MAX, max_len)
+        );
+
+        // Without a maximum length, buffers are still bounded
+        let unbounded = StoiConfig {
+            max_signal_len: None,
+            ..StoiConfig::default()
+        };
+        assert_eq!(check_length(max_len + 1, FS, &unbounded), Ok(()));
+        assert_eq!(
+            check_length(MAX_BUFFER_LEN + 1, FS, &unbounded),
+            too_long(MAX_BUFFER_LEN + 1, MAX_BUFFER_LEN)
+        );
+    }
+
+    #[test]
+    fn compensated_averages_do_not_drift_over_many_segments() {
+        // The correlations of about 45 minutes of signal
+        let value = |band: usize, segment: usize| 0.1 + ((band + segment) % 7) as f32 * 0.11;
+        let correlations = Correlations {
+            values: Mat::from_fn(15, 200_000, value),
+            valid_frames: 200_029,
+            total_frames: 200_029,
+            ..correlations(NanPolicy::Skip)
+        };
+        let values = || {
+            correlations
+                .values
+                .col_iter()
+                .flat_map(|segment| segment.iter())
+        };
+        let count = (15 * 200_000) as f64;
+        let exact = values().map(|&v| v as f64).sum::<f64>() / count;
+        let naive = values().sum::<f32>() / count as f32;
+
+        assert!((naive as f64 - exact).abs() > 1e-4, "{naive} vs {exact}");
+        assert!((correlations.score() as f64 - exact).abs() < 1e-7);
+    }
+}
@@ -0,0 +1,72 @@
+//! Serializable STOI results
+
+use serde::{Deserialize, Serialize};
+
+use crate::{StoiConfig, errors::Result, stoi_report};
+
+/// Flat STOI result row, to write scores of many signals to CSV or JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoreRecord {
+    /// Identifier of the scored signals, such as a file name
+    pub id: String,
+    /// STOI score
+    pub score: f32,
+    /// Whether the extended STOI measure was used
+    pub extended: bool,
+    /// Sampling frequency of the signals
+    pub fs: usize,
+    /// Ratio of valid frames over total frames
+    pub coverage: f32,
+    /// Amount of frames kept after removing silent frames
+    pub valid_frames: usize,
+}
+
+/// Compute the Short-Time Objective Intelligibility (STOI) measure between two signals
+/// into a serializable record.
+///
+/// Args:
+/// * `id` - Identifier of the signals
+/// * `x` - Clean speech signal
+/// * `y` - Processed speech signal
+/// * `fs_sig` - Sampling frequency of the signals
+/// * `extended` - Whether to use the extended STOI measure
+pub fn stoi_record(
+    id: String,
+    x: &[f32],
+    y: &[f32],
+    fs_sig: usize,
+    extended: bool,
+) -> Result<ScoreRecord> {
+    let config = StoiConfig {
+        extended,
+        ..Default::default()
+    };
+    let report = stoi_report(x, y, fs_sig, &config)?;
+
+    Ok(ScoreRecord {
+        id,
+        score: report.score,
+        extended,
+        fs: fs_sig,
+        coverage: report.coverage,
+        valid_frames: report.valid_frames,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{noise, noisy};
+
+    #[test]
+    fn records_round_trip_through_serde() {
+        let x = noise(30_000, 1);
+        let y = noisy(&x, 1.0, 2);
+        let record = stoi_record("pair-1.wav".to_owned(), &x, &y, 16_000, true).unwrap();
+        assert_eq!(record.score, crate::stoi(&x, &y, 16_000, true).unwrap());
+
+        let json = serde_json::to_string(&record).unwrap();
+        assert!(json.contains(r#""id":"pair-1.wav""#), "{json}");
+        assert_eq!(serde_json::from_str::<ScoreRecord>(&json).unwrap(), record);
+    }
+}
@@ -0,0 +1,361 @@
+//! Slow reference STOI implementation, for testing only.
+//!
+//! This is a deliberately simple port of the reference STOI implementation,
+//! computed in `f64` with straightforward loops: direct convolution for
+//! resampling, a direct DFT instead of an FFT, and no caching. It is orders of
+//! magnitude slower than [`crate::stoi`], and only meant to validate that the
+//! optimized pipeline does not silently diverge. Do not use it in production.
+//!
+//! It supports the default configuration only.
+//!
+//! It shares no code with the optimized pipeline, only the error type:
+//! the parameters, input checks, resampling filter design and one-third
+//! octave bands are derived again here from the reference implementation,
+//! so that a bug in one of them cannot hide in both paths. Two deliberate
+//! differences with the reference implementation remain, both shared with
+//! the optimized pipeline:
+//! * Frames exactly 40dB below the most energetic frame are kept (`>=`),
+//!   where the reference discards them (`>`).
+//! * Resampled signals have `floor(len * up / down)` samples, where
+//!   `scipy.signal.resample_poly` rounds up. The extra sample only changes
+//!   the frames for a few lengths that are not multiples of the ratio.
+
+use std::f64::consts::PI;
+
+use crate::errors::{Result, StoiError};
+
+/// Internal sampling frequency
+const FS: usize = 10_000;
+/// Frame length in samples
+const N_FRAME: usize = 256;
+/// Hop between frames in samples
+const HOP: usize = N_FRAME / 2;
+/// FFT length in samples
+const NFFT: usize = 512;
+/// Amount of one-third octave bands
+const NUM_BANDS: usize = 15;
+/// Center frequency of the lowest band in Hz
+const MIN_FREQ: f64 = 150.0;
+/// Amount of frames in a segment
+const N: usize = 30;
+/// Lower signal to distortion ratio bound in dB
+const BETA: f64 = -15.0;
+/// Speech dynamic range in dB of silent frame removal
+const DYN_RANGE: f64 = 40.0;
+/// Stopband rejection of the resampling filter in dB
+const REJECTION_DB: f64 = 60.0;
+
+/// Compute the Short-Time Objective Intelligibility (STOI) measure between
+/// two signals with the slow reference implementation.
+///
+/// Args:
+/// * `x` - Clean speech signal
+/// * `y` - Processed speech signal
+/// * `fs_sig` - Sampling frequency of the signals
+/// * `extended` - Whether to use the extended STOI measure
+pub fn stoi(x: &[f32], y: &[f32], fs_sig: usize, extended: bool) -> Result<f32> {
+    if fs_sig == 0 {
+        return Err(StoiError::InvalidSampleRate(fs_sig));
+    }
+    if x.len() != y.len() {
+        return Err(StoiError::LengthMismatch {
+            x_len: x.len(),
+            y_len: y.len(),
+        });
+    }
+
+    let x = resample(x, fs_sig);
+    let y = resample(y, fs_sig);
+    if x.len() <= N_FRAME {
+        return Err(too_short(x.len()));
+    }
+
+    let (x, y) = remove_silent_frames(&x, &y)?;
+    let bands = third_octave_bands();
+    let x_bands = octave_bands(&stft(&x), &bands);
+    let y_bands = octave_bands(&stft(&y), &bands);
+
+    let num_frames = x_bands[0].len();
+    if num_frames < N {
+        return Err(StoiError::NotEnoughFrames {
+            valid_frames: num_frames,
+            segment_frames: N,
+        });
+    }
+
+    let mut total = 0.0;
+    let num_segments = num_frames - N + 1;
+    for end in N..=num_frames {
+        let x_segment = segment(&x_bands, end);
+        let y_segment = segment(&y_bands, end);
+        total += if extended {
+            extended_correlation(x_segment, y_segment)
+        } else {
+            standard_correlation(&x_segment, &y_segment)
+        };
+    }
+
+    Ok((total / num_segments as f64) as f32)
+}
+
+/// Error for signals of `len` samples at 10kHz that have no frame
+fn too_short(len: usize) -> StoiError {
+    StoiError::SignalTooShort {
+        len,
+        fs: FS,
+        // A segment of frames, plus the discarded last frame
+        min_len: N_FRAME + 1 + N * HOP,
+    }
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// Modified Bessel function of the first kind of order 0, by its series
+fn bessel_i0(x: f64) -> f64 {
+    let (mut sum, mut term) = (1.0, 1.0);
+    let mut k = 1.0;
+    while term > sum * 1e-17 {
+        term *= (x / (2.0 * k)).powi(2);
+        sum += term;
+        k += 1.0;
+    }
+    sum
+}
+
+/// Low-pass filter of the reference `resample_oct`: an ideal sinc at the lowest
+/// Nyquist frequency, apodized by a Kaiser window designed as `kaiserord` for
+/// a 60dB rejection and a transition band of a tenth of the cutoff, and
+/// normalized to a unit sum
+fn resampling_filter(up: usize, down: usize) -> Vec<f64> {
+    let cutoff = 1.0 / (2.0 * up.max(down) as f64);
+    let roll_off_width = cutoff / 10.0;
+    let half_length = ((REJECTION_DB - 8.0) / (28.714 * roll_off_width)).ceil() as i64;
+    let beta = 0.1102 * (REJECTION_DB - 8.7);
+
+    let filter: Vec<f64> = (-half_length..=half_length)
+        .map(|t| {
+            let sinc = if t == 0 {
+                1.0
+            } else {
+                let arg = PI * 2.0 * cutoff * t as f64;
+                arg.sin() / arg
+            };
+            let ratio = t as f64 / half_length as f64;
+            let kaiser = bessel_i0(beta * (1.0 - ratio * ratio).sqrt()) / bessel_i0(beta);
+            2.0 * up as f64 * cutoff * sinc * kaiser
+        })
+        .collect();
+
+    let sum: f64 = filter.iter().sum();
+    filter.iter().map(|h| h / sum).collect()
+}
+
+/// Resample a signal to 10kHz by direct convolution with the upsampled signal
+fn resample(x: &[f32], fs_sig: usize) -> Vec<f64> {
+    if fs_sig == FS {
+        return x.iter().map(|&v| v as f64).collect();
+    }
+
+    let gcd = gcd(fs_sig, FS);
+    let (up, down) = (FS / gcd, fs_sig / gcd);
+    let filter = resampling_filter(up, down);
+    let center = filter.len() / 2;
+
+    (0..x.len() * up / down)
+        .map(|i| {
+            // Sum over the nonzero samples of the zero-stuffed signal,
+            // which are read by every `up`-th tap
+            let position = i * down + center;
+            let mut sum = 0.0;
+            for k in (position % up..filter.len().min(position + 1)).step_by(up) {
+                let n = (position - k) / up;
+                if n < x.len() {
+                    sum += filter[k] * x[n] as f64;
+                }
+            }
+            sum * up as f64
+        })
+        .collect()
+}
+
+/// Hann window without its zero endpoints
+fn hann() -> Vec<f64> {
+    (1..=N_FRAME)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / (N_FRAME + 1) as f64).cos())
+        .collect()
+}
+
+/// Windowed frames of a signal
+fn frames(x: &[f64]) -> Vec<Vec<f64>> {
+    let window = hann();
+    (0..x.len() - N_FRAME)
+        .step_by(HOP)
+        .map(|start| (0..N_FRAME).map(|t| window[t] * x[start + t]).collect())
+        .collect()
+}
+
+fn norm(x: &[f64]) -> f64 {
+    x.iter().map(|v| v * v).sum::<f64>().sqrt()
+}
+
+/// Remove the frames of both signals where the clean signal is silent,
+/// and overlap-add the remaining frames
+fn remove_silent_frames(x: &[f64], y: &[f64]) -> Result<(Vec<f64>, Vec<f64>)> {
+    let x_frames = frames(x);
+    let y_frames = frames(y);
+
+    let energies: Vec<f64> = x_frames
+        .iter()
+        .map(|frame| 20.0 * (norm(frame) + f64::EPSILON).log10())
+        .collect();
+    let max_energy = energies.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    if max_energy <= 20.0 * f64::EPSILON.log10() {
+        return Err(StoiError::SilentSignal);
+    }
+
+    let kept: Vec<usize> = (0..energies.len())
+        .filter(|&i| energies[i] >= max_energy - DYN_RANGE)
+        .collect();
+
+    let overlap_add = |frames: &[Vec<f64>]| {
+        let mut signal = vec![0.0; (kept.len() - 1) * HOP + N_FRAME];
+        for (j, &i) in kept.iter().enumerate() {
+            for t in 0..N_FRAME {
+                signal[j * HOP + t] += frames[i][t];
+            }
+        }
+        signal
+    };
+
+    Ok((overlap_add(&x_frames), overlap_add(&y_frames)))
+}
+
+/// Squared magnitude spectrogram computed with a direct DFT, with shape (frames, bins)
+fn stft(x: &[f64]) -> Vec<Vec<f64>> {
+    if x.len() <= N_FRAME {
+        return Vec::new();
+    }
+
+    // Twiddle factors, indexed by `(k * t) % NFFT`
+    let twiddles: Vec<(f64, f64)> = (0..NFFT)
+        .map(|j| {
+            let angle = -2.0 * PI * j as f64 / NFFT as f64;
+            (angle.cos(), angle.sin())
+        })
+        .collect();
+
+    frames(x)
+        .iter()
+        .map(|frame| {
+            (0..NFFT / 2 + 1)
+                .map(|k| {
+                    let (mut re, mut im) = (0.0, 0.0);
+                    for (t, v) in frame.iter().enumerate() {
+                        let (cos, sin) = twiddles[(k * t) % NFFT];
+                        re += v * cos;
+                        im += v * sin;
+                    }
+                    re * re + im * im
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// FFT bin ranges of the one-third octave bands, from the bins nearest to
+/// the band edges as the reference `thirdoct`
+fn third_octave_bands() -> Vec<(usize, usize)> {
+    let bin_freq = |bin: usize| (bin * FS) as f64 / NFFT as f64;
+    let nearest_bin = |freq: f64| {
+        (0..=NFFT / 2)
+            .min_by(|&a, &b| {
+                (bin_freq(a) - freq)
+                    .powi(2)
+                    .total_cmp(&(bin_freq(b) - freq).powi(2))
+            })
+            .unwrap()
+    };
+
+    (0..NUM_BANDS)
+        .map(|k| {
+            let low = MIN_FREQ * 2.0_f64.powf((2 * k) as f64 / 6.0 - 1.0 / 6.0);
+            let high = MIN_FREQ * 2.0_f64.powf((2 * k) as f64 / 6.0 + 1.0 / 6.0);
+            (nearest_bin(low), nearest_bin(high))
+        })
+        .collect()
+}
+
+/// One-third octave band energies, with shape (bands, frames)
+fn octave_bands(spectrogram: &[Vec<f64>], bands: &[(usize, usize)]) -> Vec<Vec<f64>> {
+    bands
+        .iter()
+        .map(|&(start, end)| {
+            spectrogram
+                .iter()
+                .map(|bins| bins[start..end].iter().sum::<f64>().sqrt())
+                .collect()
+        })
+        .collect()
+}
+
+/// Segment of band energies ending at frame `end`, with shape (bands, frames)
+fn segment(bands: &[Vec<f64>], end: usize) -> Vec<Vec<f64>> {
+    bands
+        .iter()
+        .map(|band| band[end - N..end].to_vec())
+        .collect()
+}
+
+/// Center and normalize a vector
+fn normalized(x: &[f64]) -> Vec<f64> {
+    let mean = x.iter().sum::<f64>() / x.len() as f64;
+    let centered: Vec<f64> = x.iter().map(|v| v - mean).collect();
+    let norm = norm(&centered) + f64::EPSILON;
+    centered.iter().map(|v| v / norm).collect()
+}
+
+/// Mean over bands of the correlations of the clipped, normalized processed
+/// segment with the clean segment
+fn standard_correlation(x_segment: &[Vec<f64>], y_segment: &[Vec<f64>]) -> f64 {
+    let clip = 10.0_f64.powf(-BETA / 20.0);
+    let mut total = 0.0;
+
+    for (x_band, y_band) in x_segment.iter().zip(y_segment) {
+        let ratio = norm(x_band) / (norm(y_band) + f64::EPSILON);
+        let y_clipped: Vec<f64> = x_band
+            .iter()
+            .zip(y_band)
+            .map(|(x, y)| (y * ratio).min(x * (1.0 + clip)))
+            .collect();
+
+        let x_band = normalized(x_band);
+        let y_band = normalized(&y_clipped);
+        total += x_band.iter().zip(&y_band).map(|(x, y)| x * y).sum::<f64>();
+    }
+
+    total / NUM_BANDS as f64
+}
+
+/// Mean correlation of segments normalized over frames, then over bands
+fn extended_correlation(mut x_segment: Vec<Vec<f64>>, mut y_segment: Vec<Vec<f64>>) -> f64 {
+    for segment in [&mut x_segment, &mut y_segment] {
+        for band in segment.iter_mut() {
+            *band = normalized(band);
+        }
+        for t in 0..N {
+            let frame: Vec<f64> = segment.iter().map(|band| band[t]).collect();
+            for (band, value) in segment.iter_mut().zip(normalized(&frame)) {
+                band[t] = value;
+            }
+        }
+    }
+
+    let mut total = 0.0;
+    for (x_band, y_band) in x_segment.iter().zip(&y_segment) {
+        total += x_band.iter().zip(y_band).map(|(x, y)| x * y).sum::<f64>();
+    }
+
+    total / N as f64
+}
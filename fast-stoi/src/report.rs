@@ -0,0 +1,40 @@
+//! Detailed STOI results
+
+use crate::constants::FS;
+
+/// STOI score along with statistics about the frames it was computed on.
+///
+/// STOI computed on very little speech has a high variance.
+/// As a rule of thumb, scores with a `coverage` below 0.5 or computed
+/// on less than ~1 second of speech should be considered unreliable.
+#[derive(Debug, Clone, PartialEq)]
+#[must_use]
+pub struct StoiReport {
+    /// STOI score
+    pub score: f32,
+    /// Amount of frames kept after removing silent frames
+    pub valid_frames: usize,
+    /// Amount of frames before removing silent frames
+    pub total_frames: usize,
+    /// Ratio of valid frames over total frames
+    pub coverage: f32,
+    /// Duration of speech in seconds the score was computed on
+    pub speech_seconds: f32,
+}
+
+impl StoiReport {
+    pub(crate) fn new(
+        score: f32,
+        valid_frames: usize,
+        total_frames: usize,
+        hop_length: usize,
+    ) -> Self {
+        Self {
+            score,
+            valid_frames,
+            total_frames,
+            coverage: valid_frames as f32 / total_frames as f32,
+            speech_seconds: (valid_frames * hop_length) as f32 / FS as f32,
+        }
+    }
+}
@@ -1,20 +1,202 @@
 //! Sinc poly resampling
 
-use std::f32::consts::PI;
+use std::{
+    f32::consts::PI,
+    hash::{Hash, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use dashmap::DashMap;
 use lazy_static::lazy_static;
 use num::integer;
 use windowfunctions::{Symmetry, WindowFunction, window};
 
-use crate::upfirdn::upfirdn;
+use crate::{
+    constants::MAX_BUFFER_LEN,
+    errors::{Result, StoiError},
+    fft_resample::resample_fft,
+    upfirdn::upfirdn,
+};
 
 lazy_static! {
-    /// Cache filters for different from rates
-    static ref WINDOWS: DashMap<usize, Vec<f32>> = DashMap::new();
+    /// Cache filters for different (up, down) resampling ratios and qualities
+    static ref WINDOWS: DashMap<(usize, usize, ResampleQuality), Vec<f32>> = DashMap::new();
 }
 
+/// Amount of resampling filter lookups found in the cache
+static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+/// Amount of resampling filter lookups missing from the cache
+static CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+
 const REJECTION_DB: f32 = 60.0;
+const AUTO_MIN_REJECTION_DB: f32 = 40.0;
+const AUTO_MAX_REJECTION_DB: f32 = 100.0;
+
+/// Resampling algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ResampleMethod {
+    /// Polyphase filtering, as scipy.signal.resample_poly in the reference STOI
+    #[default]
+    Polyphase,
+    /// FFT resampling, as scipy.signal.resample (see [`resample_fft`])
+    Fft,
+}
+
+/// Polyphase resampling filter design.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum ResampleQuality {
+    /// Kaiser filter with 60dB rejection and a transition band of a tenth
+    /// of the cutoff, as scipy.signal.resample_poly in the reference STOI
+    #[default]
+    Reference,
+    /// Kaiser filter with explicit parameters, for matching another resampler.
+    /// The filter has `2 * half_length + 1` taps at the highest of both rates,
+    /// and the cutoff remains the lowest of both Nyquist frequencies.
+    /// Configurations require a strictly positive `half_length`, and a finite
+    /// positive `beta`.
+    Custom { half_length: usize, beta: f32 },
+    /// Kaiser filter with a stopband rejection chosen from the resampling ratio,
+    /// with the same cutoff and transition band as the reference design.
+    ///
+    /// Downsampling by `r = from / to` folds `r - 1` stopband widths onto the
+    /// passband: the rejection is `60 + 10 * log10(r - 1)` dB, which keeps the
+    /// aliased power of a white input 60dB below the signal, clamped to `[40, 100]`.
+    /// Large downsampling ratios thus get more attenuation (66dB from 48kHz),
+    /// while ratios close to 1 get shorter filters (50dB from 11025Hz).
+    /// Upsampling keeps the 60dB of the reference: its filter rejects the
+    /// spectral images of the input, which would otherwise leak into the
+    /// upper bands.
+    Auto,
+}
+
+/// Extension of the signal edges in polyphase resampling, which the filter
+/// reads beyond the first and last samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ResamplePadding {
+    /// Zeros, as scipy.signal.resample_poly in the reference STOI
+    /// (`padtype="constant"`). Signals that do not start or end near zero
+    /// then get a transient of about a filter length at their edges.
+    #[default]
+    Zero,
+    /// Reflection of the signal about its edge samples, as `np.pad(x, mode="reflect")`.
+    /// This avoids the transient of a step to zero, at the cost of a slight
+    /// ringing of the mirrored content, and departs from the reference:
+    /// the first and last frames hold different samples. Edges are a larger
+    /// share of short clips, whose scores change the most.
+    Reflect,
+}
+
+// Qualities are cache keys: betas are compared and hashed by their bits,
+// so that every beta (NaN included) equals itself, and 0.0 differs from -0.0
+impl PartialEq for ResampleQuality {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ResampleQuality::Reference, ResampleQuality::Reference)
+            | (ResampleQuality::Auto, ResampleQuality::Auto) => true,
+            (
+                ResampleQuality::Custom { half_length, beta },
+                ResampleQuality::Custom {
+                    half_length: other_half_length,
+                    beta: other_beta,
+                },
+            ) => half_length == other_half_length && beta.to_bits() == other_beta.to_bits(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for ResampleQuality {}
+
+impl Hash for ResampleQuality {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            ResampleQuality::Reference => 0.hash(state),
+            ResampleQuality::Custom { half_length, beta } => {
+                1.hash(state);
+                half_length.hash(state);
+                beta.to_bits().hash(state);
+            }
+            ResampleQuality::Auto => 2.hash(state),
+        }
+    }
+}
+
+/// Resample a signal from `from` to `to` with the given method.
+pub fn resample_with(x: &[f32], from: usize, to: usize, method: ResampleMethod) -> Vec<f32> {
+    match method {
+        ResampleMethod::Polyphase => resample(x, from, to),
+        ResampleMethod::Fft => resample_fft(x, from, to),
+    }
+}
+
+/// Statistics about the cached resampling filters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Amount of cached filters
+    pub entries: usize,
+    /// Approximate memory used by the cached filters, in bytes
+    pub approx_bytes: usize,
+}
+
+/// Get statistics about the cached resampling filters.
+///
+/// Filters are cached for every resampling ratio and never evicted,
+/// which can be monitored in long-running processes.
+/// The statistics are a snapshot, and may miss filters being inserted concurrently.
+pub fn resample_cache_stats() -> CacheStats {
+    WINDOWS.iter().fold(
+        CacheStats {
+            entries: 0,
+            approx_bytes: 0,
+        },
+        |stats, entry| CacheStats {
+            entries: stats.entries + 1,
+            approx_bytes: stats.approx_bytes
+                + size_of::<(usize, usize, ResampleQuality)>()
+                + size_of::<Vec<f32>>()
+                + entry.value().len() * size_of::<f32>(),
+        },
+    )
+}
+
+/// Sampling frequencies whose scores are validated against the reference.
+const VALIDATED_SAMPLE_RATES: [usize; 9] = [
+    8_000, 10_000, 11_025, 16_000, 22_050, 24_000, 32_000, 44_100, 48_000,
+];
+
+/// Sampling frequencies whose scores are validated against the reference,
+/// in increasing order.
+///
+/// Any strictly positive sampling frequency works: signals are resampled for
+/// any rational ratio to 10kHz. But scores are only checked to match the
+/// reference at these rates, within 1e-7 on random signals (by the python
+/// bindings tests against `pystoi` at 8kHz, 16kHz and 32kHz, and against
+/// `reference::stoi` at all of them). Other rates use different resampling
+/// filters, which are expected but not checked to match as well.
+pub fn supported_sample_rates() -> &'static [usize] {
+    &VALIDATED_SAMPLE_RATES
+}
+
+/// Get the (hits, misses) counts of resampling filter lookups in the cache,
+/// to check that warming the cache up covers the actual sampling frequencies.
+///
+/// Every polyphase resampling looks its filter up once, and misses generate it.
+/// Counters are process-global, shared by all threads since the start of the
+/// process or the last [`reset_resample_cache_metrics`]. Concurrent misses
+/// on the same filter each count as a miss.
+pub fn resample_cache_metrics() -> (u64, u64) {
+    (
+        CACHE_HITS.load(Ordering::Relaxed),
+        CACHE_MISSES.load(Ordering::Relaxed),
+    )
+}
+
+/// Reset the resampling filter cache hit and miss counters to zero.
+/// Cached filters are kept.
+pub fn reset_resample_cache_metrics() {
+    CACHE_HITS.store(0, Ordering::Relaxed);
+    CACHE_MISSES.store(0, Ordering::Relaxed);
+}
 
 /// Generate an ideal sinc low-pass filter with normalized cutoff frequency f.
 /// Returns an iterator over the filter coefficients to avoid allocation.
@@ -32,16 +214,24 @@ fn ideal_sinc(f: f32, half_length: usize) -> impl Iterator<Item = f32> {
 /// Returns an iterator over the window coefficients to avoid allocation.
 fn kaiser(beta: f32, half_length: usize) -> impl Iterator<Item = f32> {
     window(
-        2 * half_length + 1,
+        filter_taps(half_length).expect("resampling filter length overflows usize"),
         WindowFunction::Kaiser { beta },
         Symmetry::Symmetric,
     )
 }
 
+/// Taps `2 * half_length + 1` of a filter, or `None` if it could not be allocated
+fn filter_taps(half_length: usize) -> Option<usize> {
+    half_length
+        .checked_mul(2)?
+        .checked_add(1)
+        .filter(|&taps| taps <= MAX_BUFFER_LEN)
+}
+
 /// Generates an apodized Kaiser window collected into a Row.
 fn apodized_kaiser_window(f: f32, beta: f32, half_length: usize) -> Vec<f32> {
     let sinc_iter = ideal_sinc(f, half_length);
-    let kaiser_iter = kaiser(beta as f32, half_length);
+    let kaiser_iter = kaiser(beta, half_length);
 
     sinc_iter
         .zip(kaiser_iter)
@@ -50,22 +240,109 @@ fn apodized_kaiser_window(f: f32, beta: f32, half_length: usize) -> Vec<f32> {
 }
 
 /// Generates the different contiguous filter phases for efficient
-/// computation. Also returns the filter half-length.
-fn generate_filter_phases(up: usize, down: usize) -> Vec<f32> {
-    let stopband_cutoff_freq = 1.0 / (2.0 * up.max(down) as f32);
-    let roll_off_width = stopband_cutoff_freq / 10.0;
+/// computation, with the Kaiser parameters of `quality`.
+///
+/// As in the reference implementation, the low-pass cutoff is the lowest of
+/// both Nyquist frequencies, with a transition band of a tenth of the cutoff
+/// centered on it, and a 60dB stopband rejection.
+///
+/// Measured with pure tones resampled to 10kHz from 16kHz to 48kHz:
+/// - the passband is flat up to 4750Hz (-1.6dB at 4900Hz)
+/// - tones from 5250Hz up to the input Nyquist frequency are attenuated by at least 59.9dB
+/// - tones between 5000Hz and 5250Hz are only partially attenuated, but alias
+///   between 4750Hz and 5000Hz, above the highest STOI octave band (4277Hz).
+///
+/// Panics if the filter is too long to be allocated, for huge sampling
+/// frequencies or custom half lengths (see [`filter_length`]).
+pub(crate) fn generate_filter_phases(up: usize, down: usize, quality: ResampleQuality) -> Vec<f32> {
+    let (stopband_cutoff_freq, filter_half_length, beta) = filter_design(up, down, quality);
 
     // Compute the filter
-    let filter_half_length = ((REJECTION_DB - 8.0) / (28.714 * roll_off_width)).ceil() as u32;
-    let beta = 0.1102 * (REJECTION_DB - 8.7);
-    let mut filter =
-        apodized_kaiser_window(stopband_cutoff_freq, beta, filter_half_length as usize);
+    let mut filter = apodized_kaiser_window(stopband_cutoff_freq, beta, filter_half_length);
     let sum: f32 = filter.iter().sum();
     filter.iter_mut().for_each(|v| *v /= sum);
 
     filter
 }
 
+/// Cutoff frequency, half length and Kaiser beta of the filter resampling
+/// by `up / down` with `quality`
+fn filter_design(up: usize, down: usize, quality: ResampleQuality) -> (f32, usize, f32) {
+    let stopband_cutoff_freq = 1.0 / (2.0 * up.max(down) as f32);
+
+    let roll_off_width = stopband_cutoff_freq / 10.0;
+    let (filter_half_length, beta) = match quality {
+        ResampleQuality::Reference => kaiser_design(REJECTION_DB, roll_off_width),
+        ResampleQuality::Custom { half_length, beta } => (half_length, beta),
+        ResampleQuality::Auto => kaiser_design(auto_rejection_db(up, down), roll_off_width),
+    };
+
+    (stopband_cutoff_freq, filter_half_length, beta)
+}
+
+/// Length of the polyphase filter resampling from `from` to `to` with `quality`,
+/// or `None` if it is too long to be allocated.
+///
+/// Filters get longer with the resampling ratio (about `73 * max(up, down)`
+/// taps with the reference design), so that `2 * half_length + 1` overflows
+/// for huge sampling frequencies.
+pub(crate) fn filter_length(from: usize, to: usize, quality: ResampleQuality) -> Option<usize> {
+    let (up, down) = resampling_ratio(from, to);
+    let (_, half_length, _) = filter_design(up, down, quality);
+    filter_taps(half_length)
+}
+
+/// Kaiser filter half length and beta reaching a stopband rejection of `rejection_db`
+/// with a transition band of normalized width `roll_off_width`, as scipy's kaiserord.
+fn kaiser_design(rejection_db: f32, roll_off_width: f32) -> (usize, f32) {
+    let half_length = ((rejection_db - 8.0) / (28.714 * roll_off_width)).ceil() as usize;
+    let beta = if rejection_db > 50.0 {
+        0.1102 * (rejection_db - 8.7)
+    } else if rejection_db > 21.0 {
+        0.5842 * (rejection_db - 21.0).powf(0.4) + 0.07886 * (rejection_db - 21.0)
+    } else {
+        0.0
+    };
+    (half_length, beta)
+}
+
+/// Stopband rejection of [`ResampleQuality::Auto`] resampling by `up / down`
+fn auto_rejection_db(up: usize, down: usize) -> f32 {
+    // Upsampling rejects images rather than aliases, as the reference design
+    if down <= up {
+        return REJECTION_DB;
+    }
+    let folded = down as f32 / up as f32 - 1.0;
+    (REJECTION_DB + 10.0 * folded.log10()).clamp(AUTO_MIN_REJECTION_DB, AUTO_MAX_REJECTION_DB)
+}
+
+/// Upsampling and downsampling ratios `(up, down)` from `from` to `to`,
+/// reduced by their greatest common divisor.
+///
+/// Panics if either sampling frequency is zero, which has no resampling ratio.
+pub fn resampling_ratio(from: usize, to: usize) -> (usize, usize) {
+    assert!(
+        from > 0 && to > 0,
+        "Sampling frequencies must be strictly positive (from {}Hz to {}Hz)",
+        from,
+        to
+    );
+
+    let gcd = integer::gcd(from, to);
+    (to / gcd, from / gcd)
+}
+
+/// Length of a signal of length `len` resampled from `from` to `to`.
+///
+/// The length is computed without overflow, and saturates to `usize::MAX`
+/// when it does not fit (such a signal could not be allocated anyway).
+/// Panics if either sampling frequency is zero.
+pub fn resampled_len(len: usize, from: usize, to: usize) -> usize {
+    let (up, down) = resampling_ratio(from, to);
+    let len = len as u128 * up as u128 / down as u128;
+    usize::try_from(len).unwrap_or(usize::MAX)
+}
+
 /// Polyphase resampling.
 ///
 /// About this resampling operation:
@@ -75,24 +352,406 @@ fn generate_filter_phases(up: usize, down: usize) -> Vec<f32> {
 /// - low-pass => when upsampling by inserting zeros, if we upsample times n,
 ///   we create high frequency signals.
 ///   The window must smooth them out and remove these high frequencies
+///
+/// Sampling frequencies must be strictly positive: as all resampling functions,
+/// this panics if `from` or `to` is zero. [`try_resample`] and the scoring
+/// functions return [`StoiError::InvalidSampleRate`] instead.
 pub fn resample(x: &[f32], from: usize, to: usize) -> Vec<f32> {
+    resample_polyphase(x, from, to, ResampleQuality::Reference)
+}
+
+/// Polyphase resampling as [`resample`], for untrusted sampling frequencies.
+///
+/// Returns [`StoiError::InvalidSampleRate`] if `from` or `to` is zero, or with
+/// the highest of both if the filter is too long to be allocated, where the
+/// other resampling functions panic.
+pub fn try_resample(x: &[f32], from: usize, to: usize) -> Result<Vec<f32>> {
+    for fs in [from, to] {
+        if fs == 0 {
+            return Err(StoiError::InvalidSampleRate(fs));
+        }
+    }
+    if filter_length(from, to, ResampleQuality::Reference).is_none() {
+        return Err(StoiError::InvalidSampleRate(from.max(to)));
+    }
+
+    Ok(resample(x, from, to))
+}
+
+/// Polyphase resampling to exactly `expected_len` samples, to match the
+/// fixed output length of another tool.
+///
+/// The natural output of [`resample`] is trimmed or zero-extended at its tail.
+/// Note that trimming discards valid samples: expected lengths should only
+/// differ from the natural length by rounding, of a sample or so.
+pub fn resample_exact(x: &[f32], from: usize, to: usize, expected_len: usize) -> Vec<f32> {
+    let mut y = resample(x, from, to);
+    y.resize(expected_len, 0.0);
+    y
+}
+
+/// Polyphase resampling with the filter design of `quality`.
+///
+/// Filters are cached by resampling ratio and quality: every distinct
+/// custom filter adds an entry to the cache, which is never evicted.
+pub fn resample_polyphase(x: &[f32], from: usize, to: usize, quality: ResampleQuality) -> Vec<f32> {
+    resample_padded(x, from, to, quality, ResamplePadding::Zero)
+}
+
+/// Polyphase resampling with the filter design of `quality`,
+/// extending the signal edges according to `padding`.
+///
+/// The output has the same length as [`resample_polyphase`]. With reflection,
+/// the signal is extended by a multiple of the downsampling ratio covering the
+/// filter half length at both ends, so that the output samples stay aligned.
+pub fn resample_padded(
+    x: &[f32],
+    from: usize,
+    to: usize,
+    quality: ResampleQuality,
+    padding: ResamplePadding,
+) -> Vec<f32> {
     // Compute upsampling and dowsampling ratios
-    let gcd = integer::gcd(from, to);
-    let up = to / gcd;
-    let down = from / gcd;
+    let (up, down) = resampling_ratio(from, to);
 
     // Get the filters
     // If filters are missing, they are inserted and fetched
     // again to drop the exclusive mutable ref held by entry
-    let filter = match WINDOWS.get(&from) {
-        Some(f) => f,
+    let key = (up, down, quality);
+    let filter = match WINDOWS.get(&key) {
+        Some(f) => {
+            CACHE_HITS.fetch_add(1, Ordering::Relaxed);
+            f
+        }
         None => {
+            CACHE_MISSES.fetch_add(1, Ordering::Relaxed);
             let _ = WINDOWS
-                .entry(from)
-                .or_insert_with(|| generate_filter_phases(up, down));
-            WINDOWS.get(&from).unwrap()
+                .entry(key)
+                .or_insert_with(|| generate_filter_phases(up, down, quality));
+            WINDOWS.get(&key).unwrap()
         }
     };
 
-    upfirdn(&filter, x, up, down)
+    if padding == ResamplePadding::Zero || x.len() < 2 {
+        return upfirdn(&filter, x, up, down);
+    }
+
+    // Input samples the filter reads beyond each edge, rounded up to whole
+    // downsampling steps so that they map to a whole amount of output samples
+    let extension = (filter.len() / (2 * up) + 1).div_ceil(down) * down;
+    let period = 2 * (x.len() - 1);
+    let reflect = |i: usize| {
+        // Index into the signal mirrored about its first and last samples
+        let i = (i + period - extension % period) % period;
+        x[if i < x.len() { i } else { period - i }]
+    };
+    let extended: Vec<f32> = (0..x.len() + 2 * extension).map(reflect).collect();
+
+    let start = extension * up / down;
+    let len = resampled_len(x.len(), from, to);
+    upfirdn(&filter, &extended, up, down)[start..start + len].to_vec()
+}
+
+/// Polyphase resampling without the filter cache.
+///
+/// The filter is generated again on every call and never inserted in the
+/// shared cache. This is slower by design, and meant for profiling the
+/// filter generation or for single-shot callers that don't want to keep
+/// filters in memory.
+pub fn resample_no_cache(x: &[f32], from: usize, to: usize) -> Vec<f32> {
+    // Compute upsampling and dowsampling ratios
+    let (up, down) = resampling_ratio(from, to);
+
+    upfirdn(
+        &generate_filter_phases(up, down, ResampleQuality::Reference),
+        x,
+        up,
+        down,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::DefaultHasher;
+
+    use super::*;
+    use crate::constants::FS;
+
+    fn hash(quality: ResampleQuality) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        quality.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Modified Bessel function of the first kind of order 0, by its series
+    fn bessel_i0(x: f64) -> f64 {
+        let (mut sum, mut term) = (1.0, 1.0);
+        for k in 1..100 {
+            term *= (x / 2.0) * (x / 2.0) / (k * k) as f64;
+            sum += term;
+        }
+        sum
+    }
+
+    #[test]
+    fn custom_filter_matches_apodized_kaiser_window() {
+        let (half_length, beta) = (40, 5.0);
+        let (up, down) = resampling_ratio(16_000, FS);
+        let filter =
+            generate_filter_phases(up, down, ResampleQuality::Custom { half_length, beta });
+
+        // Sinc low-pass at the lowest Nyquist frequency, times a Kaiser window
+        let cutoff = 1.0 / (2.0 * up.max(down) as f64);
+        let expected: Vec<f64> = (-(half_length as i64)..=half_length as i64)
+            .map(|n| {
+                let sinc = if n == 0 {
+                    2.0 * cutoff
+                } else {
+                    (2.0 * std::f64::consts::PI * cutoff * n as f64).sin()
+                        / (std::f64::consts::PI * n as f64)
+                };
+                let ratio = n as f64 / half_length as f64;
+                sinc * bessel_i0(beta as f64 * (1.0 - ratio * ratio).sqrt())
+                    / bessel_i0(beta as f64)
+            })
+            .collect();
+        let sum: f64 = expected.iter().sum();
+
+        assert_eq!(filter.len(), 2 * half_length + 1);
+        for (&tap, &expected) in filter.iter().zip(&expected) {
+            assert!((tap as f64 - expected / sum).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn custom_qualities_compare_betas_by_bits() {
+        let quality = |beta| ResampleQuality::Custom {
+            half_length: 10,
+            beta,
+        };
+
+        assert_eq!(quality(f32::NAN), quality(f32::NAN));
+        assert_eq!(hash(quality(f32::NAN)), hash(quality(f32::NAN)));
+        assert_ne!(quality(0.0), quality(-0.0));
+        assert_eq!(quality(5.0), quality(5.0));
+        assert_eq!(hash(quality(5.0)), hash(quality(5.0)));
+        assert_ne!(quality(5.0), ResampleQuality::Reference);
+    }
+
+    #[test]
+    fn auto_quality_adapts_the_filter_to_the_ratio() {
+        let taps = |from| {
+            let (up, down) = resampling_ratio(from, FS);
+            (
+                auto_rejection_db(up, down),
+                generate_filter_phases(up, down, ResampleQuality::Auto).len(),
+            )
+        };
+        let (up, down) = resampling_ratio(48_000, FS);
+        let reference_taps = generate_filter_phases(up, down, ResampleQuality::Reference).len();
+
+        let (rejection_48k, taps_48k) = taps(48_000);
+        let (rejection_11k, taps_11k) = taps(11_025);
+        assert!((rejection_48k - 65.8).abs() < 0.1);
+        assert!((rejection_11k - 50.1).abs() < 0.1);
+        assert!(taps_48k > reference_taps);
+        assert_ne!(taps_48k, taps_11k);
+
+        // Upsampling keeps the reference rejection and filter
+        for from in [5_000, 8_000] {
+            let (up, down) = resampling_ratio(from, FS);
+            assert_eq!(auto_rejection_db(up, down), REJECTION_DB);
+            assert_eq!(
+                generate_filter_phases(up, down, ResampleQuality::Auto),
+                generate_filter_phases(up, down, ResampleQuality::Reference)
+            );
+        }
+    }
+
+    #[test]
+    fn huge_sampling_frequencies_have_no_filter() {
+        let taps = filter_length(48_000, FS, ResampleQuality::Reference).unwrap();
+        assert_eq!(taps, 2 * 870 + 1);
+
+        assert_eq!(
+            filter_length(usize::MAX, FS, ResampleQuality::Reference),
+            None
+        );
+        assert_eq!(
+            filter_length(FS, usize::MAX - 1, ResampleQuality::Auto),
+            None
+        );
+        let quality = ResampleQuality::Custom {
+            half_length: usize::MAX / 2,
+            beta: 5.0,
+        };
+        assert_eq!(filter_length(16_000, FS, quality), None);
+
+        assert_eq!(
+            try_resample(&[0.0; 16], usize::MAX, FS),
+            Err(StoiError::InvalidSampleRate(usize::MAX))
+        );
+    }
+
+    #[test]
+    fn resampled_lengths_saturate() {
+        assert_eq!(resampled_len(usize::MAX, 1, 48_000), usize::MAX);
+        assert_eq!(resampled_len(usize::MAX, 48_000, 1), usize::MAX / 48_000);
+    }
+
+    #[test]
+    fn nan_beta_filters_are_cached_without_panicking() {
+        let quality = ResampleQuality::Custom {
+            half_length: 10,
+            beta: f32::NAN,
+        };
+        let x = vec![0.5; 1000];
+
+        resample_polyphase(&x, 16_000, FS, quality);
+        resample_polyphase(&x, 16_000, FS, quality);
+        assert!(WINDOWS.contains_key(&(5, 8, quality)));
+    }
+
+    #[test]
+    fn two_rates_insert_two_cached_filters() {
+        // A quality no other test uses, as the cache is shared by the tests
+        let quality = ResampleQuality::Custom {
+            half_length: 7,
+            beta: 3.25,
+        };
+        let keys = [(400, 441, quality), (200, 441, quality)];
+        let x = vec![0.5; 1000];
+        let before = resample_cache_stats();
+
+        resample_polyphase(&x, 11_025, FS, quality);
+        resample_polyphase(&x, 22_050, FS, quality);
+        resample_polyphase(&x, 22_050, FS, quality);
+        let after = resample_cache_stats();
+
+        assert!(keys.iter().all(|key| WINDOWS.contains_key(key)));
+        assert!(after.entries >= before.entries + 2);
+        let filters: usize = keys.iter().map(|key| WINDOWS.get(key).unwrap().len()).sum();
+        assert!(after.approx_bytes >= before.approx_bytes + filters * size_of::<f32>());
+    }
+
+    #[test]
+    fn uncached_resampling_matches_without_inserting_filters() {
+        // A rate no other test uses, as the cache is shared by the tests
+        let from = 7_840;
+        let (up, down) = resampling_ratio(from, FS);
+        let key = (up, down, ResampleQuality::Reference);
+        let x: Vec<f32> = (0..2_000).map(|i| (i as f32 * 0.05).sin()).collect();
+
+        let uncached = resample_no_cache(&x, from, FS);
+        assert!(!WINDOWS.contains_key(&key));
+
+        assert_eq!(uncached, resample(&x, from, FS));
+        assert!(WINDOWS.contains_key(&key));
+    }
+
+    /// Gain in dB of a pure tone of frequency `freq` resampled from `from` to 10kHz,
+    /// away from the signal edges
+    fn tone_gain_db(freq: f32, from: usize) -> f32 {
+        let x: Vec<f32> = (0..from / 4)
+            .map(|i| (2.0 * PI * freq * i as f32 / from as f32).sin())
+            .collect();
+        let y = resample(&x, from, FS);
+
+        let rms = |v: &[f32]| (v.iter().map(|s| s * s).sum::<f32>() / v.len() as f32).sqrt();
+        let n = y.len();
+        20.0 * (rms(&y[n / 10..9 * n / 10]) / rms(&x)).log10()
+    }
+
+    #[test]
+    fn downsampling_a_swept_sine_does_not_alias() {
+        for from in [16_000, 22_050, 32_000, 44_100, 48_000] {
+            // Flat passband, including the highest octave band (up to 4277Hz)
+            for freq in (100..=4_750).step_by(250) {
+                let gain = tone_gain_db(freq as f32, from);
+                assert!(gain.abs() < 0.1, "{from}Hz, {freq}Hz tone: {gain}dB");
+            }
+            // Tones aliasing below 4750Hz are rejected
+            for freq in (5_250..from * 49 / 100).step_by(250) {
+                let gain = tone_gain_db(freq as f32, from);
+                assert!(gain < -59.5, "{from}Hz, {freq}Hz tone: {gain}dB");
+            }
+        }
+    }
+
+    #[test]
+    fn exact_resampling_trims_or_extends_the_tail() {
+        let x = crate::test_signals::noise(16_000, 1);
+        let natural = resample(&x, 16_000, FS);
+        assert_eq!(natural.len(), 10_000);
+
+        let shorter = resample_exact(&x, 16_000, FS, 9_999);
+        assert_eq!(shorter, natural[..9_999]);
+
+        let longer = resample_exact(&x, 16_000, FS, 10_001);
+        assert_eq!(longer[..10_000], natural);
+        assert_eq!(longer[10_000], 0.0);
+    }
+
+    #[test]
+    fn cold_lookups_miss_and_warm_lookups_hit() {
+        // A quality no other test uses, as the cache and counters are shared by the tests
+        let quality = ResampleQuality::Custom {
+            half_length: 9,
+            beta: 4.75,
+        };
+        let x = vec![0.5; 1000];
+        assert!(!WINDOWS.contains_key(&(5, 8, quality)));
+
+        // No test resets the counters, so that other tests may only increase them
+        let (hits, misses) = resample_cache_metrics();
+        resample_polyphase(&x, 16_000, FS, quality);
+        let (cold_hits, cold_misses) = resample_cache_metrics();
+        assert!(cold_misses > misses);
+        assert!(cold_hits >= hits);
+
+        resample_polyphase(&x, 16_000, FS, quality);
+        resample_polyphase(&x, 16_000, FS, quality);
+        let (warm_hits, warm_misses) = resample_cache_metrics();
+        assert!(warm_hits >= cold_hits + 2);
+        assert!(warm_misses >= cold_misses);
+    }
+
+    #[test]
+    fn reflection_padding_removes_the_edge_transient_of_a_ramp() {
+        // A 0.1s ramp from 0.5 to 1 at 16kHz, sampled at 10kHz
+        let ramp = |t: f32| 0.5 + 0.5 * t / 0.1;
+        let x: Vec<f32> = (0..1_600).map(|i| ramp(i as f32 / 16_000.0)).collect();
+        let expected: Vec<f32> = (0..1_000).map(|i| ramp(i as f32 / 10_000.0)).collect();
+
+        let zero = resample_padded(
+            &x,
+            16_000,
+            FS,
+            ResampleQuality::Reference,
+            ResamplePadding::Zero,
+        );
+        let reflect = resample_padded(
+            &x,
+            16_000,
+            FS,
+            ResampleQuality::Reference,
+            ResamplePadding::Reflect,
+        );
+        assert_eq!(
+            zero,
+            resample_polyphase(&x, 16_000, FS, ResampleQuality::Reference)
+        );
+        assert_eq!(reflect.len(), zero.len());
+
+        let max_error = |y: &[f32], range: std::ops::Range<usize>| {
+            range
+                .map(|i| (y[i] - expected[i]).abs())
+                .fold(0.0_f32, f32::max)
+        };
+        // Zeros make the first samples step down from 0.5
+        assert!(max_error(&zero, 0..20) > 5e-2);
+        assert!(max_error(&reflect, 0..20) < 1e-3);
+        // Edges do not reach the interior
+        assert_eq!(zero[100..900], reflect[100..900]);
+    }
 }
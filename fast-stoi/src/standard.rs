@@ -1,30 +1,42 @@
 //! Standard STOI computation from octave segment spectrograms
 
-use std::f32::EPSILON;
-
 use faer::prelude::*;
 
-use crate::constants::{BETA, SEGMENT_LENGTH};
+use crate::{config::CorrelationKind, correlation};
 
-/// Compute the standard STOI from octave segment spectrograms of the clean and processed signals.
+/// Compute the standard STOI correlations from octave segment spectrograms of the clean and processed signals.
 /// The segments have shapes (segment_length, num_segments * num_bands).
-pub fn from_segments(x_segments: MatMut<f32>, y_segments: MatMut<f32>) -> f32 {
-    let clip_value = 10.0_f32.powf(-BETA / 20.0);
-    let n = x_segments.ncols();
+/// Processed energies are clipped to a signal-to-distortion ratio of at least `clip_db`,
+/// and Spearman correlations are the Pearson correlations of their ranks.
+/// Returns the correlation of every segment band, with shape (num_bands, num_segments).
+pub fn from_segments(
+    x_segments: MatMut<f32>,
+    y_segments: MatMut<f32>,
+    num_bands: usize,
+    clip_db: f32,
+    kind: CorrelationKind,
+) -> Mat<f32> {
+    let clip_value = 10.0_f32.powf(-clip_db / 20.0);
+    let segment_length = x_segments.nrows() as f32;
 
-    let mut similarity = 0.0;
+    let mut correlations = Mat::<f32>::zeros(num_bands, x_segments.ncols() / num_bands);
+    let mut values = Vec::new();
+    let mut order = Vec::new();
 
     // Perform the per-segment processing
     x_segments
         .col_iter_mut()
         .zip(y_segments.col_iter_mut())
-        .for_each(|(mut x_segment, mut y_segment)| {
+        .zip(correlations.col_iter_mut().flat_map(|col| col.iter_mut()))
+        .for_each(|((mut x_segment, mut y_segment), correlation)| {
             // Normalize y so that it has the same norm as x
             // and then clip y
             // NOTE: faer's .norm_l2 is very slow for such small vectors
-            let xnorm2 = (x_segment.as_ref().iter().map(|x| x * x).sum::<f32>()).sqrt() + EPSILON;
-            let ynorm2 = (y_segment.as_ref().iter().map(|x| x * x).sum::<f32>()).sqrt() + EPSILON;
-            let ratio = xnorm2 / (ynorm2 + EPSILON);
+            let xnorm2 =
+                (x_segment.as_ref().iter().map(|x| x * x).sum::<f32>()).sqrt() + f32::EPSILON;
+            let ynorm2 =
+                (y_segment.as_ref().iter().map(|x| x * x).sum::<f32>()).sqrt() + f32::EPSILON;
+            let ratio = xnorm2 / (ynorm2 + f32::EPSILON);
             let mut x_sum = 0.0;
             let mut y_sum = 0.0;
             zip!(&x_segment, &mut y_segment).for_each(|unzip!(x, y)| {
@@ -33,9 +45,14 @@ pub fn from_segments(x_segments: MatMut<f32>, y_segments: MatMut<f32>) -> f32 {
                 y_sum += *y;
             });
 
+            if kind == CorrelationKind::Spearman {
+                x_sum = rank_segment(x_segment.rb_mut(), &mut values, &mut order);
+                y_sum = rank_segment(y_segment.rb_mut(), &mut values, &mut order);
+            }
+
             // Compute means
-            let x_mean = x_sum / SEGMENT_LENGTH as f32;
-            let y_mean = y_sum / SEGMENT_LENGTH as f32;
+            let x_mean = x_sum / segment_length;
+            let y_mean = y_sum / segment_length;
 
             // Subtract mean and start computing resulting norm
             // at the same time
@@ -48,8 +65,8 @@ pub fn from_segments(x_segments: MatMut<f32>, y_segments: MatMut<f32>) -> f32 {
                 y_sq_sum += y.powi(2);
             });
 
-            let x_norm = x_sq_sum.sqrt() + EPSILON;
-            let y_norm = y_sq_sum.sqrt() + EPSILON;
+            let x_norm = x_sq_sum.sqrt() + f32::EPSILON;
+            let y_norm = y_sq_sum.sqrt() + f32::EPSILON;
 
             // Compute pre-normalization similarity
             let mut s = 0.0;
@@ -57,9 +74,24 @@ pub fn from_segments(x_segments: MatMut<f32>, y_segments: MatMut<f32>) -> f32 {
                 s += x * y;
             });
 
-            // Aggregate similarity and apply normalization
-            similarity += s / (x_norm * y_norm);
+            // Apply normalization
+            *correlation = s / (x_norm * y_norm);
         });
 
-    similarity / n as f32
+    correlations
+}
+
+/// Replace the values of a segment by their ranks, and return the sum of the ranks.
+/// Segments may have any stride: they are ranked in the contiguous scratch
+/// buffer `values`, which is reused across segments along with `order`.
+fn rank_segment(segment: ColMut<f32>, values: &mut Vec<f32>, order: &mut Vec<usize>) -> f32 {
+    values.clear();
+    values.extend(segment.as_ref().iter().copied());
+    correlation::rank(values, order);
+
+    segment
+        .iter_mut()
+        .zip(values.iter())
+        .for_each(|(x, &rank)| *x = rank);
+    values.iter().sum()
 }
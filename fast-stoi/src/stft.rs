@@ -5,57 +5,99 @@ use std::sync::Arc;
 use faer::prelude::*;
 use lazy_static::lazy_static;
 use num::complex::ComplexFloat;
-use realfft::{RealFftPlanner, RealToComplex};
+use realfft::{RealFftPlanner, RealToComplex, num_complex::Complex};
 
 use crate::constants::{FFT_BINS, FFT_LENGTH};
 
+// The FFT length is not configurable. Frames are zero padded to an even length,
+// as the reference implementation, and the band tables assume FFT_LENGTH bins.
+const _: () = assert!(FFT_LENGTH.is_multiple_of(2), "FFT_LENGTH must be even");
+const _: () = assert!(
+    FFT_BINS == FFT_LENGTH / 2 + 1,
+    "FFT_BINS must match FFT_LENGTH"
+);
+
 lazy_static! {
     static ref R2C: Arc<dyn RealToComplex<f32>> =
         RealFftPlanner::<f32>::new().plan_fft_forward(FFT_LENGTH);
 }
 
-/// Compute the RFFT of each valid frame as indicated by the mask.
-/// Input frames have shape (frame_length, n_frames).
+/// Compute the RFFT of `count` frames of at most `FFT_LENGTH` samples.
+/// `read_frame(k, out)` writes the `k`-th frame into `out`, of length `frame_length`,
+/// which is zero padded to `FFT_LENGTH`.
 /// Returns a real valued squared magnitude spectrogram
-/// of shape (FFT_BINS, frames).
-pub fn compute_frame_rffts(frames: MatRef<f32>, mask: ColRef<bool>, count: usize) -> Mat<f32> {
+/// of shape (FFT_BINS, count).
+/// Frame lengths are not checked, see [`crate::spectrogram`] for a checked version.
+pub fn compute_frame_rffts(
+    count: usize,
+    frame_length: usize,
+    read_frame: impl FnMut(usize, &mut [f32]),
+) -> Mat<f32> {
+    // Create output array as column-major for faster writes
+    let mut spectrogram = Mat::<f32>::zeros(FFT_BINS, count);
+
+    for_each_frame_rfft(count, frame_length, read_frame, |k, spectrum| {
+        // Copy squared magnitude spectrum to output spectrogram
+        spectrogram
+            .col_mut(k)
+            .iter_mut()
+            .zip(spectrum)
+            .for_each(|(real, complex)| {
+                *real = complex.re().powi(2) + complex.im().powi(2);
+            });
+    });
+
+    spectrogram
+}
+
+/// Compute the RFFT of `count` frames of at most `FFT_LENGTH` samples, as
+/// [`compute_frame_rffts`], returning the log-magnitude spectrogram in dB
+/// (`20 * log10(|X|)`) of shape (FFT_BINS, count).
+/// Values are raised to at least `floor_db` after the log, so that zero bins
+/// yield `floor_db` instead of `-inf`.
+pub fn compute_frame_log_rffts(
+    count: usize,
+    frame_length: usize,
+    read_frame: impl FnMut(usize, &mut [f32]),
+    floor_db: f32,
+) -> Mat<f32> {
+    let mut spectrogram = compute_frame_rffts(count, frame_length, read_frame);
+
+    // Squared magnitudes need no sqrt: 20 * log10(|X|) = 10 * log10(|X|²)
+    spectrogram
+        .col_iter_mut()
+        .flat_map(|col| col.iter_mut())
+        .for_each(|power| *power = (10.0 * power.log10()).max(floor_db));
+
+    spectrogram
+}
+
+/// Compute the RFFT of `count` frames of at most `FFT_LENGTH` samples,
+/// calling `on_spectrum(k, spectrum)` with the `FFT_BINS` complex bins of the `k`-th frame.
+/// `read_frame(k, out)` writes the `k`-th frame into `out`, of length `frame_length`,
+/// which is zero padded to `FFT_LENGTH`.
+pub fn for_each_frame_rfft(
+    count: usize,
+    frame_length: usize,
+    mut read_frame: impl FnMut(usize, &mut [f32]),
+    mut on_spectrum: impl FnMut(usize, &[Complex<f32>]),
+) {
     // Create buffers
     let mut scratch_buffer = R2C.make_scratch_vec();
     let mut input_buffer = R2C.make_input_vec();
     let mut output_buffer = R2C.make_output_vec();
 
-    // Create output array as column-major for faster writes
-    let mut spectrogram = Mat::<f32>::zeros(FFT_BINS, count);
-    let mut index = 0; // destination row index (skips invalid frames)
-
-    // Iterate over valid frames and compute their RFFT
-    frames
-        .col_iter()
-        .zip(mask.iter())
-        .for_each(|(frame, &valid)| {
-            if !valid {
-                return;
-            }
-
-            // Copy frame into input buffer with zero padding
-            input_buffer[..frame.nrows()]
-                .copy_from_slice(frame.try_as_col_major().unwrap().as_slice());
-
-            // Perform RFFT
-            R2C.process_with_scratch(&mut input_buffer, &mut output_buffer, &mut scratch_buffer)
-                .unwrap();
-
-            // Copy squared magnitude spectrum to output spectrogram
-            spectrogram
-                .col_mut(index)
-                .iter_mut()
-                .zip(&output_buffer)
-                .for_each(|(real, complex)| {
-                    *real = complex.re().powi(2) + complex.im().powi(2);
-                });
-
-            index += 1;
-        });
+    for k in 0..count {
+        // Read frame into input buffer with zero padding.
+        // The FFT uses its input as scratch space: the padding is reset every time.
+        let (frame, padding) = input_buffer.split_at_mut(frame_length);
+        read_frame(k, frame);
+        padding.fill(0.0);
 
-    spectrogram
+        // Perform RFFT
+        R2C.process_with_scratch(&mut input_buffer, &mut output_buffer, &mut scratch_buffer)
+            .unwrap();
+
+        on_spectrum(k, &output_buffer);
+    }
 }
@@ -0,0 +1,109 @@
+//! Spectrogram images for visual diagnostics
+
+use crate::{
+    constants::{DYNAMIC_RANGE, FFT_BINS, FFT_LENGTH, FS},
+    frames, resample, stft,
+};
+
+/// Compute an RGBA image of the log-magnitude spectrogram of a signal,
+/// as STOI analyzes it after resampling to 10kHz.
+///
+/// Frames of `frame_length` samples (at most 512) are taken every `hop_length`
+/// samples at 10kHz, and windowed with the STOI Hann window before their FFT.
+///
+/// Returns the pixels in row-major order along with the image width and height.
+/// Columns are frames in time order, and rows are the 257 FFT bins
+/// from 5kHz at the top to 0Hz at the bottom.
+///
+/// Color mapping: power is converted to dB relative to the loudest bin,
+/// and the 40dB below it (the STOI dynamic range) are mapped linearly from black
+/// through red and yellow to white. Quieter bins are black.
+pub fn spectrogram_rgba(
+    x: &[f32],
+    fs_sig: usize,
+    frame_length: usize,
+    hop_length: usize,
+) -> (Vec<u8>, usize, usize) {
+    assert!(
+        (1..=FFT_LENGTH).contains(&frame_length),
+        "frame_length must be between 1 and {}",
+        FFT_LENGTH
+    );
+    assert!(hop_length > 0, "hop_length must be strictly positive");
+
+    let x = if fs_sig != FS {
+        resample::resample(x, fs_sig, FS)
+    } else {
+        x.to_vec()
+    };
+
+    // Windowed frames
+    let window = frames::hann(frame_length);
+    let n_frames = match x.len().checked_sub(frame_length) {
+        Some(extra) => extra / hop_length + 1,
+        None => 0,
+    };
+    let spectrogram = stft::compute_frame_rffts(n_frames, frame_length, |j, out| {
+        let start = j * hop_length;
+        out.iter_mut()
+            .zip(&x[start..start + frame_length])
+            .zip(window.iter())
+            .for_each(|((out, sample), w)| *out = sample * w);
+    });
+
+    // Power in dB relative to the loudest bin
+    let reference = spectrogram
+        .col_iter()
+        .map(|col| col.iter().copied().fold(0.0_f32, f32::max))
+        .fold(f32::MIN_POSITIVE, f32::max);
+
+    let (width, height) = (n_frames, FFT_BINS);
+    let mut pixels = vec![0; width * height * 4];
+
+    for (frame, col) in spectrogram.col_iter().enumerate() {
+        for (bin, &power) in col.iter().enumerate() {
+            let db = 10.0 * (power.max(f32::MIN_POSITIVE) / reference).log10();
+            let level = (1.0 + db / DYNAMIC_RANGE).clamp(0.0, 1.0);
+
+            let row = height - 1 - bin;
+            let offset = (row * width + frame) * 4;
+            pixels[offset..offset + 4].copy_from_slice(&heat_color(level));
+        }
+    }
+
+    (pixels, width, height)
+}
+
+/// Map a level in `[0, 1]` to a black-red-yellow-white RGBA color
+fn heat_color(level: f32) -> [u8; 4] {
+    let channel = |start: f32| ((level * 3.0 - start).clamp(0.0, 1.0) * 255.0).round() as u8;
+    [channel(0.0), channel(1.0), channel(2.0), 255]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn images_have_one_pixel_per_frame_and_bin() {
+        // 1kHz sine at 10kHz, centered on the 51.2th bin
+        let x: Vec<f32> = (0..10_000)
+            .map(|i| (2.0 * std::f32::consts::PI * 0.1 * i as f32).sin())
+            .collect();
+
+        let (pixels, width, height) = spectrogram_rgba(&x, FS, 256, 128);
+        assert_eq!((width, height), ((10_000 - 256) / 128 + 1, FFT_BINS));
+        assert_eq!(pixels.len(), width * height * 4);
+
+        // The sine is bright, and bins far from it are black
+        let pixel = |row: usize, frame: usize| {
+            let offset = (row * width + frame) * 4;
+            &pixels[offset..offset + 4]
+        };
+        assert_eq!(pixel(FFT_BINS - 1 - 51, 10)[..2], [255, 255]);
+        assert_eq!(pixel(0, 10), [0, 0, 0, 255]);
+
+        let (pixels, width, _) = spectrogram_rgba(&x[..100], FS, 256, 128);
+        assert_eq!((pixels.len(), width), (0, 0));
+    }
+}
@@ -0,0 +1,290 @@
+//! WAV decoding
+
+use std::{collections::BTreeSet, fs, io::Read, path::Path};
+
+use hound::{SampleFormat, WavReader};
+
+use crate::{
+    config::StoiConfig,
+    errors::{Result, StoiError},
+    incremental::IncrementalStoi,
+};
+
+/// Read a WAV file into a mono signal along with its sampling frequency.
+///
+/// Integer samples of any bit depth (8, 16, 24 or 32 bits) are divided by
+/// 2^(bits - 1) into `[-1, 1]`, and float samples are kept as is.
+/// Multichannel files are downmixed to mono with equal weights.
+pub fn read_wav(path: impl AsRef<Path>) -> Result<(Vec<f32>, u32)> {
+    read(WavReader::open(path).map_err(wav_error)?)
+}
+
+/// Read WAV data from memory into a mono signal along with its sampling frequency.
+///
+/// This decodes the same formats as [`read_wav`], without a temporary file.
+pub fn read_wav_bytes(data: &[u8]) -> Result<(Vec<f32>, u32)> {
+    read(WavReader::new(data).map_err(wav_error)?)
+}
+
+fn wav_error(err: hound::Error) -> StoiError {
+    StoiError::Wav(err.to_string())
+}
+
+/// Decode all samples of a WAV reader
+fn read<R: Read>(reader: WavReader<R>) -> Result<(Vec<f32>, u32)> {
+    let fs = reader.spec().sample_rate;
+    let samples = mono_samples(reader).collect::<Result<Vec<_>>>()?;
+
+    Ok((samples, fs))
+}
+
+/// Decode the samples of a WAV reader one at a time, downmixing channels
+/// as [`crate::downmix`] with equal weights
+fn mono_samples<'a, R: Read + 'a>(reader: WavReader<R>) -> impl Iterator<Item = Result<f32>> + 'a {
+    let spec = reader.spec();
+
+    let mut samples: Box<dyn Iterator<Item = hound::Result<f32>> + 'a> = match spec.sample_format {
+        SampleFormat::Float => Box::new(reader.into_samples::<f32>()),
+        SampleFormat::Int => {
+            let scale = 2.0_f32.powi(spec.bits_per_sample as i32 - 1);
+            Box::new(
+                reader
+                    .into_samples::<i32>()
+                    .map(move |sample| sample.map(|sample| sample as f32 / scale)),
+            )
+        }
+    };
+
+    // Channels are interleaved: every frame holds one sample of each channel
+    let n_channels = spec.channels as usize;
+    let weight = 1.0 / n_channels as f32;
+    std::iter::from_fn(move || {
+        let mut mono = 0.0;
+        for channel in 0..n_channels {
+            match samples.next() {
+                Some(Ok(sample)) if n_channels == 1 => return Some(Ok(sample)),
+                Some(Ok(sample)) => mono += weight * sample,
+                Some(Err(err)) => return Some(Err(wav_error(err))),
+                None if channel == 0 => return None,
+                None => return Some(Err(StoiError::Wav("truncated last frame".to_owned()))),
+            }
+        }
+        Some(Ok(mono))
+    })
+}
+
+/// Compute the Short-Time Objective Intelligibility (STOI) measure between two
+/// WAV files, decoding them in chunks.
+///
+/// Files are decoded to mono as [`read_wav`], but without holding their decoded
+/// samples, or their separate channels, at once: only the mono signals are
+/// buffered, as [`IncrementalStoi`] does. The memory used is thus the same as
+/// scoring in-memory mono signals. Scores are identical to [`read_wav`] followed
+/// by [`crate::stoi_with_config`]. Files with different sampling frequencies are
+/// read fully and scored with [`crate::stoi_rates`].
+///
+/// Returns [`StoiError::LengthMismatch`] with the amounts of samples per channel
+/// before decoding if the files have different lengths at the same sampling frequency.
+///
+/// Args:
+/// * `clean` - Clean speech WAV file
+/// * `processed` - Processed speech WAV file
+/// * `config` - Computation parameters
+pub fn stoi_wav_files(
+    clean: impl AsRef<Path>,
+    processed: impl AsRef<Path>,
+    config: &StoiConfig,
+) -> Result<f32> {
+    let x_reader = WavReader::open(&clean).map_err(wav_error)?;
+    let y_reader = WavReader::open(&processed).map_err(wav_error)?;
+    let (fs_x, fs_y) = (x_reader.spec().sample_rate, y_reader.spec().sample_rate);
+
+    if fs_x != fs_y {
+        let (x, _) = read(x_reader)?;
+        let (y, _) = read(y_reader)?;
+        return crate::stoi_rates(&x, fs_x as usize, &y, fs_y as usize, config);
+    }
+
+    if x_reader.duration() != y_reader.duration() {
+        return Err(StoiError::LengthMismatch {
+            x_len: x_reader.duration() as usize,
+            y_len: y_reader.duration() as usize,
+        });
+    }
+
+    let mut scorer = IncrementalStoi::new(fs_x as usize, config.clone())?;
+    let mut x_samples = mono_samples(x_reader);
+    let mut y_samples = mono_samples(y_reader);
+    let mut x_chunk = Vec::with_capacity(CHUNK_LENGTH);
+    let mut y_chunk = Vec::with_capacity(CHUNK_LENGTH);
+    loop {
+        x_chunk.clear();
+        y_chunk.clear();
+        for sample in x_samples.by_ref().take(CHUNK_LENGTH) {
+            x_chunk.push(sample?);
+        }
+        for sample in y_samples.by_ref().take(CHUNK_LENGTH) {
+            y_chunk.push(sample?);
+        }
+        if x_chunk.is_empty() && y_chunk.is_empty() {
+            break;
+        }
+        scorer.push(&x_chunk, &y_chunk)?;
+    }
+
+    scorer.finalize()
+}
+
+/// Amount of samples decoded at once by [`stoi_wav_files`]
+const CHUNK_LENGTH: usize = 4096;
+
+/// Score the WAV files of a processed directory against the clean WAV files
+/// with the same names in a clean directory, in the order of their names.
+///
+/// Pairs are read and scored lazily as the iterator advances, so that memory
+/// stays flat on large datasets and results can be printed as they come.
+/// Files of a directory without a match in the other (or whose name is not
+/// UTF-8) are skipped with a warning. Pairs with different sampling frequencies
+/// are scored with [`crate::stoi_rates`].
+///
+/// Returns [`StoiError::Wav`] if a directory cannot be listed.
+/// Failures to read or score a pair are yielded along with its name.
+///
+/// Args:
+/// * `clean_dir` - Directory of clean speech WAV files
+/// * `proc_dir` - Directory of processed speech WAV files
+/// * `extended` - Whether to use the extended STOI measure
+pub fn score_dir(
+    clean_dir: impl AsRef<Path>,
+    proc_dir: impl AsRef<Path>,
+    extended: bool,
+) -> Result<impl Iterator<Item = (String, Result<f32>)>> {
+    let (clean_dir, proc_dir) = (clean_dir.as_ref().to_owned(), proc_dir.as_ref().to_owned());
+    let clean_names = wav_names(&clean_dir)?;
+    let proc_names = wav_names(&proc_dir)?;
+
+    for name in clean_names.symmetric_difference(&proc_names) {
+        log::warn!("Skipping {name}: no matching file in both directories");
+    }
+
+    let config = StoiConfig {
+        extended,
+        ..Default::default()
+    };
+    let names: Vec<String> = clean_names.intersection(&proc_names).cloned().collect();
+
+    Ok(names.into_iter().map(move |name| {
+        let score = stoi_wav_files(clean_dir.join(&name), proc_dir.join(&name), &config);
+        (name, score)
+    }))
+}
+
+/// Names of the WAV files of a directory
+fn wav_names(dir: &Path) -> Result<BTreeSet<String>> {
+    let entries = fs::read_dir(dir).map_err(|err| StoiError::Wav(err.to_string()))?;
+    let mut names = BTreeSet::new();
+
+    for entry in entries {
+        let path = entry.map_err(|err| StoiError::Wav(err.to_string()))?.path();
+        let is_wav = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"));
+        if !is_wav || !path.is_file() {
+            continue;
+        }
+
+        match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => {
+                names.insert(name.to_owned());
+            }
+            None => log::warn!("Skipping {}: file name is not UTF-8", path.display()),
+        }
+    }
+
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 16-bit mono WAV file at 8kHz with the samples 0, 2^14, -2^14 and -2^15
+    const MONO_WAV: [u8; 52] = [
+        b'R', b'I', b'F', b'F', 44, 0, 0, 0, b'W', b'A', b'V', b'E', // RIFF header
+        b'f', b'm', b't', b' ', 16, 0, 0, 0, // fmt chunk
+        1, 0, 1, 0, 0x40, 0x1f, 0, 0, 0x80, 0x3e, 0, 0, 2, 0, 16, 0, // PCM, mono, 8kHz
+        b'd', b'a', b't', b'a', 8, 0, 0, 0, // data chunk
+        0x00, 0x00, 0x00, 0x40, 0x00, 0xc0, 0x00, 0x80,
+    ];
+
+    #[test]
+    fn embedded_wav_bytes_are_decoded() {
+        let (samples, fs) = read_wav_bytes(&MONO_WAV).unwrap();
+        assert_eq!(fs, 8_000);
+        assert_eq!(samples, vec![0.0, 0.5, -0.5, -1.0]);
+    }
+
+    #[test]
+    fn stereo_wav_bytes_are_downmixed() {
+        // Same data, read as 2 frames of 2 channels
+        let mut stereo = MONO_WAV;
+        stereo[22] = 2; // channels
+        stereo[28..32].copy_from_slice(&32_000u32.to_le_bytes()); // byte rate
+        stereo[32] = 4; // block align
+
+        let (samples, fs) = read_wav_bytes(&stereo).unwrap();
+        assert_eq!(fs, 8_000);
+        assert_eq!(samples, vec![0.25, -0.75]);
+    }
+
+    #[test]
+    fn truncated_wav_bytes_fail() {
+        assert!(matches!(
+            read_wav_bytes(&MONO_WAV[..20]),
+            Err(StoiError::Wav(_))
+        ));
+    }
+
+    /// Write a 32-bit float mono WAV file at 16kHz
+    fn write_wav(path: &Path, samples: &[f32]) {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 16_000,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        samples
+            .iter()
+            .for_each(|&sample| writer.write_sample(sample).unwrap());
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn directories_are_scored_by_matching_names() {
+        let root = std::env::temp_dir().join("fast-stoi-score-dir");
+        let (clean_dir, proc_dir) = (root.join("clean"), root.join("proc"));
+        fs::create_dir_all(&clean_dir).unwrap();
+        fs::create_dir_all(&proc_dir).unwrap();
+
+        let x = crate::test_signals::noise(16_000, 1);
+        let y = crate::test_signals::noisy(&x, 1.0, 2);
+        write_wav(&clean_dir.join("a.wav"), &x);
+        write_wav(&proc_dir.join("a.wav"), &y);
+        write_wav(&clean_dir.join("b.wav"), &x);
+        write_wav(&proc_dir.join("b.wav"), &x);
+        // Unmatched files and files that are not WAV files are skipped
+        write_wav(&clean_dir.join("c.wav"), &x);
+        fs::write(proc_dir.join("notes.txt"), b"").unwrap();
+
+        let results: Vec<_> = score_dir(&clean_dir, &proc_dir, false).unwrap().collect();
+        fs::remove_dir_all(&root).unwrap();
+
+        let expected = [
+            ("a.wav".to_string(), crate::stoi(&x, &y, 16_000, false)),
+            ("b.wav".to_string(), crate::stoi(&x, &x, 16_000, false)),
+        ];
+        assert_eq!(results, expected);
+        assert!(score_dir(&clean_dir, &proc_dir, false).is_err());
+    }
+}
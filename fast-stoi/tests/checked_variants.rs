@@ -0,0 +1,66 @@
+//! Error paths of the checked counterparts of the panicking building blocks
+
+mod common;
+
+use common::noise;
+use faer::Mat;
+use fast_stoi::{
+    StoiConfig, StoiError, octave_bands, resample, resample_and_frame, spectrogram, try_resample,
+};
+
+#[test]
+fn checked_resampling_rejects_zero_rates() {
+    let x = noise(1_000, 1);
+    assert_eq!(
+        try_resample(&x, 0, 10_000),
+        Err(StoiError::InvalidSampleRate(0))
+    );
+    assert_eq!(
+        try_resample(&x, 16_000, 0),
+        Err(StoiError::InvalidSampleRate(0))
+    );
+    assert_eq!(
+        try_resample(&x, 16_000, 10_000),
+        Ok(resample(&x, 16_000, 10_000))
+    );
+}
+
+#[test]
+fn checked_framing_rejects_invalid_signals() {
+    let x = noise(16_000, 2);
+    let config = StoiConfig::default();
+    assert!(matches!(
+        resample_and_frame(&x, &x[1..], 16_000, &config),
+        Err(StoiError::LengthMismatch { .. })
+    ));
+    assert!(matches!(
+        resample_and_frame(&x, &x, 0, &config),
+        Err(StoiError::InvalidSampleRate(0))
+    ));
+    let silence = vec![0.0; 16_000];
+    assert!(matches!(
+        resample_and_frame(&silence, &x, 16_000, &config),
+        Err(StoiError::SilentSignal)
+    ));
+}
+
+#[test]
+fn checked_spectrograms_reject_invalid_shapes() {
+    assert_eq!(
+        spectrogram(Mat::<f32>::zeros(513, 4).as_ref()),
+        Err(StoiError::InvalidConfig(
+            "frames length must be at most the FFT length"
+        ))
+    );
+    let spectrum = spectrogram(Mat::<f32>::zeros(256, 4).as_ref()).unwrap();
+    assert_eq!((spectrum.nrows(), spectrum.ncols()), (257, 4));
+
+    assert_eq!(
+        octave_bands(spectrum.subrows(0, 256)),
+        Err(StoiError::SpectrumTooShort {
+            bins: 256,
+            min_bins: 257,
+        })
+    );
+    assert!(octave_bands(spectrum.as_ref()).is_ok());
+}
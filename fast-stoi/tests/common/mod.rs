@@ -0,0 +1,36 @@
+//! Deterministic signals shared by the integration tests
+
+#![allow(dead_code)]
+
+/// Uniform white noise in `[-1, 1)`, from a xorshift generator seeded with `seed`
+pub fn noise(len: usize, seed: u64) -> Vec<f32> {
+    let mut state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+        })
+        .collect()
+}
+
+/// `x` with added white noise of amplitude `gain`
+pub fn noisy(x: &[f32], gain: f32, seed: u64) -> Vec<f32> {
+    x.iter()
+        .zip(noise(x.len(), seed))
+        .map(|(x, noise)| x + gain * noise)
+        .collect()
+}
+
+/// Noise bursts of `burst` samples separated by silences of `gap` samples,
+/// so that silent frame removal discards frames
+pub fn bursts(len: usize, burst: usize, gap: usize, seed: u64) -> Vec<f32> {
+    let mut x = noise(len, seed);
+    for (i, sample) in x.iter_mut().enumerate() {
+        if i % (burst + gap) >= burst {
+            *sample = 0.0;
+        }
+    }
+    x
+}
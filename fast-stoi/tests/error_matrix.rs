@@ -0,0 +1,212 @@
+//! Pathological inputs of the public entry points, each with the error it returns
+
+mod common;
+
+use common::{bursts, noise};
+use fast_stoi::{
+    FS, ResampleQuality, StoiConfig, StoiError, min_signal_len, octave_bands, stoi, stoi_multi_ref,
+    stoi_region, stoi_with_config,
+};
+
+type Case = (&'static str, fn() -> fast_stoi::Result<f32>, StoiError);
+
+fn too_short(len: usize, fs: usize) -> StoiError {
+    StoiError::SignalTooShort {
+        len,
+        fs,
+        min_len: min_signal_len(fs, &StoiConfig::default()),
+    }
+}
+
+fn cases() -> Vec<Case> {
+    vec![
+        (
+            "empty",
+            || stoi([], [], 16_000, false),
+            too_short(0, 16_000),
+        ),
+        (
+            "one sample",
+            // Reported once resampled to 10kHz, where no sample remains
+            || stoi([0.5], [0.5], 16_000, false),
+            too_short(0, FS),
+        ),
+        (
+            "all zero",
+            || stoi(vec![0.0; 16_000], noise(16_000, 1), 16_000, false),
+            StoiError::SilentSignal,
+        ),
+        (
+            "all nan",
+            || stoi(vec![f32::NAN; 16_000], noise(16_000, 1), 16_000, false),
+            StoiError::SilentSignal,
+        ),
+        (
+            "mostly silent",
+            // A single burst of 50ms, spanning 3 frames
+            || {
+                let x = bursts(16_000, 800, 15_200, 2);
+                stoi(&x, &x, 16_000, false)
+            },
+            StoiError::NotEnoughFrames {
+                valid_frames: 3,
+                segment_frames: 30,
+            },
+        ),
+        (
+            "mismatched lengths",
+            || stoi(noise(16_000, 3), noise(15_999, 4), 16_000, false),
+            StoiError::LengthMismatch {
+                x_len: 16_000,
+                y_len: 15_999,
+            },
+        ),
+        (
+            "zero rate",
+            || stoi(noise(16_000, 5), noise(16_000, 6), 0, false),
+            StoiError::InvalidSampleRate(0),
+        ),
+        (
+            "rate too high for its filter",
+            || stoi(noise(16_000, 7), noise(16_000, 8), usize::MAX, false),
+            StoiError::InvalidSampleRate(usize::MAX),
+        ),
+        (
+            "unsupported rate in strict mode",
+            || {
+                let config = StoiConfig {
+                    strict_sample_rate: true,
+                    ..Default::default()
+                };
+                stoi_with_config(noise(16_000, 9), noise(16_000, 10), 12_345, &config)
+            },
+            StoiError::InvalidSampleRate(12_345),
+        ),
+        (
+            "nan beta",
+            || {
+                let config = StoiConfig {
+                    resample_quality: ResampleQuality::Custom {
+                        half_length: 16,
+                        beta: f32::NAN,
+                    },
+                    ..Default::default()
+                };
+                stoi_with_config(noise(16_000, 11), noise(16_000, 12), 16_000, &config)
+            },
+            StoiError::InvalidConfig("resample beta must be finite and positive"),
+        ),
+        (
+            "overflowing trim",
+            || {
+                let config = StoiConfig {
+                    trim_segments: usize::MAX,
+                    ..Default::default()
+                };
+                stoi_with_config(noise(16_000, 13), noise(16_000, 14), 16_000, &config)
+            },
+            StoiError::InvalidConfig("trim_segments overflows the amount of valid frames"),
+        ),
+        (
+            "empty references",
+            || stoi_multi_ref(&[], &noise(16_000, 15), 16_000, &StoiConfig::default()).map(|r| r.0),
+            StoiError::InvalidConfig("at least one reference is required"),
+        ),
+        (
+            "too long",
+            || {
+                let config = StoiConfig {
+                    max_signal_len: Some(5_000),
+                    ..Default::default()
+                };
+                stoi_with_config(noise(16_000, 16), noise(16_000, 17), 16_000, &config)
+            },
+            StoiError::SignalTooLong {
+                len: 10_000,
+                max_len: 5_000,
+            },
+        ),
+        (
+            "clipped",
+            || {
+                let config = StoiConfig {
+                    strict_clipping: true,
+                    ..Default::default()
+                };
+                stoi_with_config(vec![1.0; 16_000], noise(16_000, 18), 16_000, &config)
+            },
+            StoiError::Clipped { count: 16_000 },
+        ),
+        (
+            "region outside of the signals",
+            || {
+                let x = noise(16_000, 19);
+                stoi_region(&x, &x, 16_000, &StoiConfig::default(), 0.5, 1.5)
+            },
+            StoiError::InvalidRegion,
+        ),
+        (
+            "spectrum too short",
+            || octave_bands(faer::Mat::<f32>::zeros(256, 4).as_ref()).map(|_| 0.0),
+            StoiError::SpectrumTooShort {
+                bins: 256,
+                min_bins: 257,
+            },
+        ),
+    ]
+}
+
+#[test]
+fn pathological_inputs_return_their_error() {
+    for (name, score, expected) in cases() {
+        assert_eq!(score(), Err(expected), "{name}");
+    }
+}
+
+#[test]
+fn pathological_inputs_never_panic() {
+    let signals: Vec<(Vec<f32>, Vec<f32>)> = vec![
+        (vec![], vec![]),
+        (vec![0.5], vec![0.5]),
+        (vec![0.0; 8_000], vec![0.0; 8_000]),
+        (vec![f32::NAN; 8_000], vec![f32::NAN; 8_000]),
+        (vec![f32::INFINITY; 8_000], noise(8_000, 21)),
+        (noise(8_000, 22), noise(7_000, 23)),
+    ];
+    let config = StoiConfig::default();
+
+    for (x, y) in &signals {
+        for fs in [0, 1, 8_000, 10_000, 12_345, usize::MAX] {
+            // Errors and degenerate scores are both fine, as long as nothing panics
+            let _ = stoi(x, y, fs, false);
+            let _ = stoi(x, y, fs, true);
+            let _ = fast_stoi::stoi_report(x, y, fs, &config);
+            let _ = fast_stoi::stoi_bands(x, y, fs, &config);
+            let _ = fast_stoi::stoi_both(x, y, fs, &config);
+            let _ = fast_stoi::stoi_relative(x, y, fs, false);
+            let _ = fast_stoi::stoi_cached(x, y, fs, &config);
+        }
+    }
+}
+
+#[cfg(feature = "wav")]
+#[test]
+fn invalid_wav_data_is_a_wav_error() {
+    assert!(matches!(
+        fast_stoi::read_wav_bytes(b"not a wav file"),
+        Err(StoiError::Wav(_))
+    ));
+}
+
+#[cfg(feature = "debug-npy")]
+#[test]
+fn unwritable_dump_directory_is_an_npy_error() {
+    // A file is in the way of the output directory
+    let file = std::env::temp_dir().join("fast-stoi-error-matrix-npy");
+    std::fs::write(&file, b"").unwrap();
+    let x = noise(16_000, 20);
+
+    let result = fast_stoi::stoi_dump(&x, &x, 16_000, false, file.join("out"));
+    std::fs::remove_file(&file).unwrap();
+    assert!(matches!(result, Err(StoiError::Npy(_))), "{result:?}");
+}
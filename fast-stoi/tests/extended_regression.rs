@@ -0,0 +1,41 @@
+//! Regression values of the extended STOI measure.
+//!
+//! The stored scores were computed by this crate, with the float64 port of the
+//! `reference` feature, and not by `pystoi`: they catch unintended changes of
+//! the extended scores and of their dependence on the signal level, but not a
+//! discrepancy with `pystoi` shared by both implementations.
+
+mod common;
+
+use common::{noise, noisy};
+
+/// Maximum difference with the regression scores
+const TOLERANCE: f32 = 1e-6;
+
+/// (seed, sampling frequency, noise gain, extended score) of 1.5s of
+/// noise against a noisy copy
+const REGRESSION_SCORES: [(u64, usize, f32, f32); 4] = [
+    (1, 16_000, 0.5, 0.758_560_8),
+    (2, 16_000, 1.0, 0.436_369_1),
+    (3, 10_000, 2.0, 0.194_994_4),
+    (4, 8_000, 1.0, 0.427_742_1),
+];
+
+#[test]
+fn extended_scores_match_the_regression_scores_at_any_level() {
+    for (seed, fs, gain, expected) in REGRESSION_SCORES {
+        let x = noise(3 * fs / 2, seed);
+        let y = noisy(&x, gain, seed + 100);
+
+        // The extended normalization must not depend on the signal level
+        for scale in [1.0, 1e-3, 1e-5] {
+            let x: Vec<f32> = x.iter().map(|sample| scale * sample).collect();
+            let y: Vec<f32> = y.iter().map(|sample| scale * sample).collect();
+            let score = fast_stoi::stoi(&x, &y, fs, true).unwrap();
+            assert!(
+                (score - expected).abs() < TOLERANCE,
+                "seed {seed} at {fs}Hz, scaled by {scale}: {score} vs {expected}"
+            );
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Scores of frames with other memory layouts than contiguous columns
+
+mod common;
+
+use common::{noise, noisy};
+use faer::{Mat, MatRef};
+use fast_stoi::{StoiConfig, resample_and_frame, stoi_from_frames};
+
+#[test]
+fn frames_of_any_layout_give_the_same_score() {
+    let x = noise(30_000, 1);
+    let y = noisy(&x, 1.0, 2);
+    let (x_frames, y_frames, _, count) =
+        resample_and_frame(&x, &y, 16_000, &StoiConfig::default()).unwrap();
+    let expected = stoi_from_frames(x_frames.as_ref(), y_frames.as_ref(), false).unwrap();
+    let n = x_frames.nrows();
+
+    // Row-major frames, whose samples are strided in memory
+    let row_major = |frames: &Mat<f32>| {
+        (0..n * count)
+            .map(|i| frames[(i / count, i % count)])
+            .collect::<Vec<_>>()
+    };
+    let (x_rows, y_rows) = (row_major(&x_frames), row_major(&y_frames));
+    let score = stoi_from_frames(
+        MatRef::from_row_major_slice(&x_rows, n, count),
+        MatRef::from_row_major_slice(&y_rows, n, count),
+        false,
+    );
+    assert_eq!(score, Ok(expected));
+
+    // Frames every other column of a larger column-major matrix
+    let interleaved = |frames: &Mat<f32>| {
+        (0..2 * n * count)
+            .map(|i| match (i / n) % 2 {
+                0 => frames[(i % n, i / (2 * n))],
+                _ => f32::NAN,
+            })
+            .collect::<Vec<_>>()
+    };
+    let (x_wide, y_wide) = (interleaved(&x_frames), interleaved(&y_frames));
+    let every_other = |data| MatRef::from_column_major_slice_with_stride(data, n, count, 2 * n);
+    let score = stoi_from_frames(every_other(&x_wide), every_other(&y_wide), false);
+    assert_eq!(score, Ok(expected));
+
+    // Rows in reverse order, with a negative row stride: time-reversed frames
+    // have the same magnitude spectra, up to rounding
+    let score = stoi_from_frames(
+        x_frames.as_ref().reverse_rows(),
+        y_frames.as_ref().reverse_rows(),
+        false,
+    )
+    .unwrap();
+    assert!((score - expected).abs() < 1e-5, "{score} vs {expected}");
+}
@@ -0,0 +1,51 @@
+//! Spearman rank correlations, for band vectors and standard scores
+
+mod common;
+
+use common::{noise, noisy};
+use fast_stoi::{CorrelationKind, StoiConfig, StoiError, pearson, spearman, stoi_with_config};
+
+#[test]
+fn spearman_of_monotonic_data_is_one() {
+    let a: Vec<f32> = (0..10).map(|i| i as f32).collect();
+    let cubic: Vec<f32> = a.iter().map(|a| a.powi(3)).collect();
+
+    assert!((spearman(&a, &cubic) - 1.0).abs() < 1e-6);
+    assert!(pearson(&a, &cubic) < 0.95);
+}
+
+#[test]
+fn tied_values_share_their_average_rank() {
+    // Ranks (4, 1, 4, 2, 4) against (1, 2, 3, 4, 5): deviations (1, -2, 1, -1, 1)
+    // and (-2, -1, 0, 1, 2), so 1 / sqrt(8 * 10)
+    let r = spearman(&[3.0, 1.0, 3.0, 2.0, 3.0], &[1.0, 2.0, 3.0, 4.0, 5.0]);
+    assert!((r - 1.0 / 80.0_f32.sqrt()).abs() < 1e-6, "{r}");
+}
+
+#[test]
+fn spearman_scores_only_apply_to_the_standard_measure() {
+    let x = noise(24_000, 1);
+    let y = noisy(&x, 1.0, 2);
+    let spearman = StoiConfig {
+        correlation: CorrelationKind::Spearman,
+        ..StoiConfig::default()
+    };
+
+    let score = stoi_with_config(&x, &y, 16_000, &spearman).unwrap();
+    let pearson = stoi_with_config(&x, &y, 16_000, &StoiConfig::default()).unwrap();
+    assert!(
+        score > 0.0 && score < 1.0 && score != pearson,
+        "{score} vs {pearson}"
+    );
+
+    let extended = StoiConfig {
+        extended: true,
+        ..spearman
+    };
+    assert_eq!(
+        stoi_with_config(&x, &y, 16_000, &extended),
+        Err(StoiError::InvalidConfig(
+            "the extended measure only supports Pearson correlations"
+        ))
+    );
+}
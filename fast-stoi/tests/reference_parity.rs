@@ -0,0 +1,128 @@
+//! Parity of the optimized pipeline with the slow reference implementation
+
+#![cfg(feature = "reference")]
+
+mod common;
+
+use common::{bursts, noise, noisy};
+use fast_stoi::{StoiError, reference};
+
+/// Maximum difference between the optimized and reference scores.
+/// Both agree within about 6e-8: the optimized pipeline runs in `f32`
+/// and sums in a different order, which this bound leaves room for.
+const TOLERANCE: f32 = 1e-6;
+
+fn assert_parity(x: &[f32], y: &[f32], fs: usize) {
+    for extended in [false, true] {
+        let fast = fast_stoi::stoi(x, y, fs, extended).unwrap();
+        let slow = reference::stoi(x, y, fs, extended).unwrap();
+        assert!(
+            (fast - slow).abs() < TOLERANCE,
+            "{fs}Hz (extended: {extended}): {fast} vs {slow}"
+        );
+    }
+}
+
+#[test]
+fn independent_noises_match_at_several_rates() {
+    for (seed, fs) in [(1, 8_000), (2, 10_000), (3, 16_000), (4, 22_050)] {
+        let len = 3 * fs / 2;
+        assert_parity(&noise(len, seed), &noise(len, seed + 100), fs);
+    }
+}
+
+#[test]
+fn every_validated_rate_matches() {
+    for (seed, &fs) in (20..).zip(fast_stoi::supported_sample_rates()) {
+        let x = noise(3 * fs / 2, seed);
+        assert_parity(&x, &noisy(&x, 1.0, seed + 100), fs);
+    }
+}
+
+#[test]
+fn noisy_signals_match_at_several_rates() {
+    for (seed, fs) in [(5, 8_000), (6, 10_000), (7, 16_000)] {
+        let x = noise(3 * fs / 2, seed);
+        for gain in [0.3, 1.0, 3.0] {
+            assert_parity(&x, &noisy(&x, gain, seed + 100), fs);
+        }
+    }
+}
+
+#[test]
+fn signals_with_silences_match() {
+    // Bursts of 150ms separated by 100ms of silence
+    for fs in [10_000, 16_000] {
+        let x = bursts(2 * fs, 3 * fs / 20, fs / 10, 8);
+        assert_parity(&x, &noisy(&x, 0.5, 9), fs);
+    }
+}
+
+#[test]
+fn lengths_that_are_not_multiples_of_the_ratio_match() {
+    for len in [23_999, 24_001, 24_123] {
+        let x = noise(len, 10);
+        assert_parity(&x, &noisy(&x, 1.0, 11), 16_000);
+    }
+}
+
+#[test]
+fn single_precision_does_not_drift_on_long_signals() {
+    // Rounding errors of the f32 pipeline must not accumulate with the length
+    let x = noise(100_000, 13);
+    assert_parity(&x, &noisy(&x, 1.0, 14), 10_000);
+}
+
+#[test]
+fn invalid_inputs_fail_in_both() {
+    let x = noise(16_000, 12);
+
+    assert_eq!(
+        reference::stoi(&x, &x, 0, false),
+        Err(StoiError::InvalidSampleRate(0))
+    );
+    assert!(matches!(
+        reference::stoi(&x, &x[1..], 16_000, false),
+        Err(StoiError::LengthMismatch { .. })
+    ));
+    assert!(matches!(
+        reference::stoi(&[], &[], 16_000, false),
+        Err(StoiError::SignalTooShort { .. })
+    ));
+    assert_eq!(
+        reference::stoi(&[0.0; 16_000], &x, 16_000, false),
+        Err(StoiError::SilentSignal)
+    );
+    assert!(matches!(
+        reference::stoi(&x[..4_000], &x[..4_000], 16_000, false),
+        Err(StoiError::NotEnoughFrames { .. })
+    ));
+}
+
+#[test]
+fn default_clipping_bound_matches_and_others_change_the_score() {
+    let x = noise(24_000, 40);
+    let y = noisy(&x, 3.0, 41);
+    let score = |extended, clip_db| {
+        let config = fast_stoi::StoiConfig {
+            extended,
+            clip_db,
+            ..Default::default()
+        };
+        fast_stoi::stoi_with_config(&x, &y, 16_000, &config).unwrap()
+    };
+
+    let slow = reference::stoi(&x, &y, 16_000, false).unwrap();
+    assert!((score(false, -15.0) - slow).abs() < TOLERANCE);
+
+    // Clipping closer to the clean energies bounds the distortion more,
+    // raising the scores of heavily distorted signals
+    let (standard, mild, tight) = (score(false, -15.0), score(false, -5.0), score(false, 0.0));
+    assert!(
+        standard < mild && mild < tight,
+        "{standard}, {mild}, {tight}"
+    );
+
+    // The extended measure does not clip
+    assert_eq!(score(true, -15.0), score(true, 0.0));
+}
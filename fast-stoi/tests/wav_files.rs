@@ -0,0 +1,66 @@
+//! Scores of WAV files decoded in chunks against fully read files
+
+#![cfg(feature = "wav")]
+
+mod common;
+
+use std::path::Path;
+
+use common::{noise, noisy};
+use fast_stoi::{StoiConfig, StoiError, read_wav, stoi_wav_files, stoi_with_config};
+use hound::{SampleFormat, WavSpec, WavWriter};
+
+/// Write interleaved samples to a 16-bit WAV file
+fn write_wav(path: &Path, samples: &[f32], channels: u16, sample_rate: u32) {
+    let spec = WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    };
+    let mut writer = WavWriter::create(path, spec).unwrap();
+    samples
+        .iter()
+        .for_each(|&sample| writer.write_sample((sample * 16_000.0) as i16).unwrap());
+    writer.finalize().unwrap();
+}
+
+#[test]
+fn wav_files_decoded_in_chunks_match_fully_read_files() {
+    let root = std::env::temp_dir().join("fast-stoi-wav-files");
+    std::fs::create_dir_all(&root).unwrap();
+    let config = StoiConfig::default();
+
+    // 10 seconds of mono at 16kHz and 3.6 seconds of stereo at 44.1kHz,
+    // spanning many decoded chunks
+    for (channels, fs) in [(1, 16_000), (2, 44_100)] {
+        let len = 160_000 * channels as usize;
+        let x = noise(len, 1);
+        let y = noisy(&x, 0.5, 2);
+        let (clean, processed) = (root.join("x.wav"), root.join("y.wav"));
+        write_wav(&clean, &x, channels, fs);
+        write_wav(&processed, &y, channels, fs);
+
+        let chunked = stoi_wav_files(&clean, &processed, &config);
+        let (x_read, fs_read) = read_wav(&clean).unwrap();
+        let (y_read, _) = read_wav(&processed).unwrap();
+        let expected = stoi_with_config(&x_read, &y_read, fs_read as usize, &config);
+        assert_eq!(chunked, expected, "{channels} channels at {fs}Hz");
+        assert!(expected.is_ok());
+    }
+
+    // Files of different lengths at the same rate
+    let (long, short) = (root.join("long.wav"), root.join("short.wav"));
+    write_wav(&long, &noise(16_000, 3), 1, 16_000);
+    write_wav(&short, &noise(1_000, 4), 1, 16_000);
+    let mismatched = stoi_wav_files(&long, &short, &config);
+    std::fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!(
+        mismatched,
+        Err(StoiError::LengthMismatch {
+            x_len: 16_000,
+            y_len: 1_000
+        })
+    );
+}
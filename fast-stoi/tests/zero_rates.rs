@@ -0,0 +1,52 @@
+//! Zero sampling frequencies in the resampling functions and the scores
+
+mod common;
+
+use std::panic::catch_unwind;
+
+use common::noise;
+use fast_stoi::{
+    ResamplePadding, ResampleQuality, StoiError, resample, resample_fft, resample_no_cache,
+    resample_padded, resample_polyphase, stoi,
+};
+
+type Resampler = fn(&[f32], usize, usize) -> Vec<f32>;
+
+#[test]
+fn resampling_zero_rates_panics_with_the_rates() {
+    let resamplers: [(&str, Resampler); 5] = [
+        ("resample", resample),
+        ("resample_no_cache", resample_no_cache),
+        ("resample_fft", resample_fft),
+        ("resample_polyphase", |x, from, to| {
+            resample_polyphase(x, from, to, ResampleQuality::Reference)
+        }),
+        ("resample_padded", |x, from, to| {
+            resample_padded(
+                x,
+                from,
+                to,
+                ResampleQuality::Reference,
+                ResamplePadding::Reflect,
+            )
+        }),
+    ];
+    let x = noise(1_000, 1);
+
+    for (name, resampler) in resamplers {
+        for (from, to) in [(0, 10_000), (16_000, 0)] {
+            let panic = catch_unwind(|| resampler(&x, from, to)).unwrap_err();
+            let message = panic.downcast_ref::<String>().unwrap();
+            assert!(
+                message.contains(&format!("from {from}Hz to {to}Hz")),
+                "{name}: {message}"
+            );
+        }
+    }
+}
+
+#[test]
+fn scores_reject_zero_rates() {
+    let x = noise(16_000, 2);
+    assert_eq!(stoi(&x, &x, 0, false), Err(StoiError::InvalidSampleRate(0)));
+}